@@ -0,0 +1,54 @@
+use super::{AuthProvider, Secret};
+use crate::error::DlError;
+use crate::hash::BASE64_ENGINE;
+use base64::Engine as _;
+use futures::future::{ready, BoxFuture, FutureExt};
+use url::Url;
+
+/// HTTP Basic credentials sent to every host asked for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: Secret,
+}
+
+impl BasicAuth {
+    pub fn new<U: Into<String>, P: Into<Secret>>(username: U, password: P) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    /// ```
+    /// use file_download::auth::BasicAuth;
+    /// let auth = BasicAuth::new("Aladdin", "open sesame");
+    /// assert_eq!(auth.header_value().expose(), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+    /// ```
+    pub fn header_value(&self) -> Secret {
+        let credentials = format!("{}:{}", self.username, self.password.expose());
+        format!("Basic {}", BASE64_ENGINE.encode(credentials)).into()
+    }
+}
+
+impl AuthProvider for BasicAuth {
+    fn authorization<'a>(&'a self, _: &'a Url) -> BoxFuture<'a, Result<Option<Secret>, DlError>> {
+        ready(Ok(Some(self.header_value()))).boxed()
+    }
+}
+
+/// A static bearer token.
+#[derive(Debug, Clone)]
+pub struct BearerToken(pub Secret);
+
+impl BearerToken {
+    pub fn new<T: Into<Secret>>(token: T) -> Self {
+        Self(token.into())
+    }
+}
+
+impl AuthProvider for BearerToken {
+    fn authorization<'a>(&'a self, _: &'a Url) -> BoxFuture<'a, Result<Option<Secret>, DlError>> {
+        ready(Ok(Some(format!("Bearer {}", self.0.expose()).into()))).boxed()
+    }
+}
@@ -0,0 +1,126 @@
+use super::{AuthProvider, BasicAuth, Secret};
+use crate::error::DlError;
+use futures::future::{ready, BoxFuture, FutureExt};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[derive(Debug, Clone)]
+struct NetrcMachine {
+    host: String,
+    auth: BasicAuth,
+}
+
+/// Basic credentials looked up by host in a `.netrc` file.
+#[derive(Debug, Clone, Default)]
+pub struct NetrcAuth {
+    machines: Vec<NetrcMachine>,
+    default: Option<BasicAuth>,
+}
+
+impl NetrcAuth {
+    /// Parses the content of a netrc file, `macdef` bodies are ignored.
+    ///
+    /// ```
+    /// use file_download::auth::NetrcAuth;
+    /// let netrc = NetrcAuth::parse(
+    ///     "machine example.com login me password secret\ndefault login anonymous password guest",
+    /// );
+    /// assert_eq!(netrc.credentials("example.com").unwrap().username, "me");
+    /// assert_eq!(netrc.credentials("other.com").unwrap().username, "anonymous");
+    /// ```
+    pub fn parse(content: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut in_macdef = false;
+        for line in content.lines() {
+            if in_macdef {
+                in_macdef = !line.trim().is_empty();
+                continue;
+            }
+            for word in line.split_whitespace() {
+                if word.starts_with('#') {
+                    break;
+                }
+                if word == "macdef" {
+                    in_macdef = true;
+                    break;
+                }
+                tokens.push(word);
+            }
+        }
+
+        let mut netrc = Self::default();
+        // host, login and password of the entry being read, no host for `default`
+        let mut entry: Option<(Option<&str>, &str, &str)> = None;
+        let mut tokens = tokens.into_iter();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" => {
+                    netrc.push(entry.take());
+                    entry = tokens.next().map(|host| (Some(host), "", ""));
+                }
+                "default" => {
+                    netrc.push(entry.take());
+                    entry = Some((None, "", ""));
+                }
+                "login" | "password" | "account" => {
+                    let value = tokens.next().unwrap_or_default();
+                    match (token, entry.as_mut()) {
+                        ("login", Some(entry)) => entry.1 = value,
+                        ("password", Some(entry)) => entry.2 = value,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        netrc.push(entry);
+        netrc
+    }
+
+    fn push(&mut self, entry: Option<(Option<&str>, &str, &str)>) {
+        match entry {
+            Some((Some(host), login, password)) => self.machines.push(NetrcMachine {
+                host: host.to_owned(),
+                auth: BasicAuth::new(login, password),
+            }),
+            Some((None, login, password)) => self.default = Some(BasicAuth::new(login, password)),
+            None => {}
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        std::fs::read_to_string(path).map(|content| Self::parse(&content))
+    }
+
+    /// Reads the file named by `$NETRC`, or `~/.netrc`.
+    pub fn from_env() -> std::io::Result<Self> {
+        let path = match std::env::var_os("NETRC") {
+            Some(path) => PathBuf::from(path),
+            None => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".netrc"))
+                .ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "HOME is not set")
+                })?,
+        };
+        Self::from_file(path)
+    }
+
+    /// Credentials of `host`, or of the `default` entry.
+    pub fn credentials(&self, host: &str) -> Option<&BasicAuth> {
+        self.machines
+            .iter()
+            .find(|machine| machine.host.eq_ignore_ascii_case(host))
+            .map(|machine| &machine.auth)
+            .or(self.default.as_ref())
+    }
+}
+
+impl AuthProvider for NetrcAuth {
+    fn authorization<'a>(&'a self, url: &'a Url) -> BoxFuture<'a, Result<Option<Secret>, DlError>> {
+        let header = url
+            .host_str()
+            .and_then(|host| self.credentials(host))
+            .map(BasicAuth::header_value);
+        ready(Ok(header)).boxed()
+    }
+}
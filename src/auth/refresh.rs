@@ -0,0 +1,66 @@
+use super::{AuthProvider, Secret};
+use crate::error::{DlError, ThreadSafeError};
+use futures::future::{BoxFuture, FutureExt};
+use futures::lock::Mutex;
+use std::future::Future;
+use url::Url;
+
+type TokenFetcher =
+    Box<dyn Fn() -> BoxFuture<'static, Result<String, ThreadSafeError>> + Send + Sync>;
+
+/// Bearer token obtained from an async callback, fetched on first use and
+/// fetched again when the server answers `401`.
+pub struct RefreshableToken {
+    fetch: TokenFetcher,
+    token: Mutex<Option<Secret>>,
+}
+
+impl RefreshableToken {
+    pub fn new<F, Fut>(fetch: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, ThreadSafeError>> + Send + 'static,
+    {
+        Self {
+            fetch: Box::new(move || fetch().boxed()),
+            token: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_token(&self) -> Result<Secret, DlError> {
+        (self.fetch)()
+            .await
+            .map(Secret::from)
+            .map_err(DlError::AuthError)
+    }
+}
+
+impl std::fmt::Debug for RefreshableToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RefreshableToken").finish_non_exhaustive()
+    }
+}
+
+impl AuthProvider for RefreshableToken {
+    fn authorization<'a>(&'a self, _: &'a Url) -> BoxFuture<'a, Result<Option<Secret>, DlError>> {
+        async move {
+            let mut token = self.token.lock().await;
+            if token.is_none() {
+                *token = Some(self.fetch_token().await?);
+            }
+            Ok(token
+                .as_ref()
+                .map(|token| format!("Bearer {}", token.expose()).into()))
+        }
+        .boxed()
+    }
+
+    fn refresh<'a>(&'a self, _: &'a Url) -> BoxFuture<'a, Result<bool, DlError>> {
+        async move {
+            let fresh = self.fetch_token().await?;
+            *self.token.lock().await = Some(fresh);
+            Ok(true)
+        }
+        .boxed()
+    }
+}
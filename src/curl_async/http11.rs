@@ -10,9 +10,13 @@ use std::{
 
 pub type Easy2Builder<H> = Box<dyn Send + 'static + FnOnce() -> Result<Easy2<H>, CurlError>>;
 
+/// The handle of a performed transfer with its result, so the handler can
+/// still be inspected after a failure.
+pub type Transfer<H> = (Easy2<H>, Result<(), curl::Error>);
+
 enum DlHttp1FutureState<H: Handler> {
     NotStarted(Easy2Builder<H>),
-    Pending(std::thread::JoinHandle<Result<Easy2<H>, ThreadSafeError>>),
+    Pending(std::thread::JoinHandle<Result<Transfer<H>, CurlError>>),
     Done,
 }
 impl <H: Handler> std::fmt::Debug for DlHttp1FutureState<H> {
@@ -43,7 +47,7 @@ impl<H: Handler + Send + 'static> DlHttp1Future<H> {
 }
 
 impl<H: Handler + Send + 'static + std::fmt::Debug> Future for DlHttp1Future<H> {
-    type Output = Result<Easy2<H>, CurlError>;
+    type Output = Result<Transfer<H>, CurlError>;
 
     #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug"))]
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
@@ -59,16 +63,13 @@ impl<H: Handler + Send + 'static + std::fmt::Debug> Future for DlHttp1Future<H>
             if let DlHttp1FutureState::NotStarted(easy_builder) = state {
                 let cx2 = cx.waker().clone();
                 let mut state = DlHttp1FutureState::Pending(std::thread::spawn(move || {
-                    easy_builder()
-                        .and_then(|easy| match easy.perform() {
-                            Ok(_) => Ok(easy),
-                            Err(e) => Err(e.into()),
-                        })
-                        .map_err(|err| ThreadSafeError::from(format!("curl error occured {}", err)))
-                        .map(move |easy| {
-                            cx2.wake();
-                            easy
-                        })
+                    let result = easy_builder().map(|easy| {
+                        let performed = easy.perform();
+                        (easy, performed)
+                    });
+                    // failures must wake the task too, or it would never be polled again
+                    cx2.wake();
+                    result
                 }));
                 std::mem::swap(&mut self_m.state, &mut state);
                 // We are back in a valid state
@@ -96,9 +97,7 @@ impl<H: Handler + Send + 'static + std::fmt::Debug> Future for DlHttp1Future<H>
             std::mem::swap(&mut self_m.state, &mut done);
             match done {
                 // this branch calls thread.join() wich is non-blocking on completed threads
-                DlHttp1FutureState::Pending(thread) => {
-                    Poll::Ready(thread.join().expect("join").map_err(CurlError::from))
-                }
+                DlHttp1FutureState::Pending(thread) => Poll::Ready(thread.join().expect("join")),
                 _ => panic!("bad state"),
             }
         }
@@ -13,10 +13,13 @@ use std::{
     time::Duration,
 };
 
+/// Result of every transfer of a [`DlHttp2Future`], in the order of the handles.
+pub type TransferResults = Vec<Result<(), curl::Error>>;
+
 #[derive(Debug)]
-enum DlHttp2FutureState<'files, T: Handler + std::fmt::Debug> {
+enum DlHttp2FutureState {
     Pending,
-    Done(&'files [Easy2Handle<T>]),
+    Done(TransferResults),
     Error(Arc<CurlError>),
 }
 
@@ -28,29 +31,51 @@ enum DlHttp2FutureState<'files, T: Handler + std::fmt::Debug> {
 struct DlHttp2FutureInner<'files, T: Handler + std::fmt::Debug> {
     pub files: Option<&'files [Easy2Handle<T>]>,
     pub multi: Option<curl::multi::Multi>,
-    pub state: DlHttp2FutureState<'files, T>,
+    pub results: Vec<Option<Result<(), curl::Error>>>,
+    pub state: DlHttp2FutureState,
     pub join: Option<std::thread::JoinHandle<()>>,
 }
 
 impl<'files, T: Handler + std::fmt::Debug> DlHttp2FutureInner<'files, T> {
+    /// Transfers without a completion message are reported as successful.
+    fn take_results(&mut self) -> TransferResults {
+        mem::take(&mut self.results)
+            .into_iter()
+            .map(|result| result.unwrap_or(Ok(())))
+            .collect()
+    }
+
+    fn read_messages(&mut self) {
+        if let (Some(multi), Some(files)) = (&self.multi, self.files) {
+            let results = &mut self.results;
+            multi.messages(|message| {
+                for (result, file) in results.iter_mut().zip(files.iter()) {
+                    if let Some(transfer) = message.result_for2(file) {
+                        *result = Some(transfer);
+                    }
+                }
+            });
+        }
+    }
+
     #[cfg_attr(feature = "tracing", tracing::instrument)]
     fn poll_multi(&mut self) {
         if let DlHttp2FutureState::Pending = self.state {
             if self.files.map(|a| a.is_empty()).unwrap_or(true) {
-                let mut files = None;
-                mem::swap(&mut files, &mut self.files);
-                self.state = DlHttp2FutureState::Done(files.unwrap());
+                self.files = None;
+                self.state = DlHttp2FutureState::Done(self.take_results());
                 let mut multi = None;
                 mem::swap(&mut self.multi, &mut multi);
                 drop(multi);
                 return;
             }
             if let Some(multi) = &mut self.multi {
-                match multi.perform() {
+                let performed = multi.perform();
+                self.read_messages();
+                match performed {
                     Ok(0) => {
-                        let mut files = None;
-                        mem::swap(&mut files, &mut self.files);
-                        self.state = DlHttp2FutureState::Done(files.unwrap());
+                        self.files = None;
+                        self.state = DlHttp2FutureState::Done(self.take_results());
                         let mut multi = None;
                         mem::swap(&mut self.multi, &mut multi);
                         drop(multi);
@@ -67,12 +92,12 @@ impl<'files, T: Handler + std::fmt::Debug> DlHttp2FutureInner<'files, T> {
         }
     }
 
-    fn poll(&mut self, cx: &mut Context) -> Poll<Result<&'files [Easy2Handle<T>], Arc<CurlError>>> {
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<TransferResults, Arc<CurlError>>> {
         if let DlHttp2FutureState::Pending = self.state {
             self.poll_multi();
         }
         match &self.state {
-            DlHttp2FutureState::Done(files) => Poll::Ready(Ok(<&[Easy2Handle<T>]>::clone(files))),
+            DlHttp2FutureState::Done(results) => Poll::Ready(Ok(results.clone())),
             DlHttp2FutureState::Error(error) => Poll::Ready(Err(error.clone())),
             _ => {
                 let ct = cx.waker().clone();
@@ -110,7 +135,8 @@ impl<'files, T: Handler + std::fmt::Debug> DlHttp2Future<'files, T> {
                 inner: DlHttp2FutureInner {
                     files: None,
                     multi: None,
-                    state: DlHttp2FutureState::Done(files),
+                    results: Vec::new(),
+                    state: DlHttp2FutureState::Done(Vec::new()),
                     join: None,
                 },
             };
@@ -119,6 +145,7 @@ impl<'files, T: Handler + std::fmt::Debug> DlHttp2Future<'files, T> {
         Self {
             inner: DlHttp2FutureInner {
                 state: DlHttp2FutureState::Pending,
+                results: files.iter().map(|_| None).collect(),
                 files: Some(files),
                 multi: Some(multi),
                 join: None,
@@ -128,7 +155,7 @@ impl<'files, T: Handler + std::fmt::Debug> DlHttp2Future<'files, T> {
 }
 
 impl<'files, T: Handler + std::fmt::Debug> Future for DlHttp2Future<'files, T> {
-    type Output = Result<&'files [Easy2Handle<T>], Arc<CurlError>>;
+    type Output = Result<TransferResults, Arc<CurlError>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
         self.get_mut().inner.poll(cx)
@@ -1,5 +1,5 @@
 mod http11;
 mod http2;
 
-pub use http11::DlHttp1Future;
-pub use http2::DlHttp2Future;
+pub use http11::{DlHttp1Future, Transfer};
+pub use http2::{DlHttp2Future, TransferResults};
@@ -18,9 +18,19 @@ pub struct BadCheckSumErrorDetail {
     pub current_hash: String,
 }
 
+/// A file of another size than [`crate::FileToDl::expected_size`], a transfer
+/// aborted for sending too much reports the bytes received until then.
+#[derive(Debug)]
+pub struct BadSizeErrorDetail {
+    pub url: String,
+    pub expected_size: u64,
+    pub current_size: u64,
+}
+
 #[derive(Debug)]
 pub struct BadCheckSumError {
     pub file_sources: Vec<BadCheckSumErrorDetail>,
+    pub file_sizes: Vec<BadSizeErrorDetail>,
 }
 impl Display for BadCheckSumError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
@@ -31,10 +41,168 @@ impl Error for BadCheckSumError {}
 
 impl From<Vec<BadCheckSumErrorDetail>> for BadCheckSumError {
     fn from(file_sources: Vec<BadCheckSumErrorDetail>) -> Self {
+        Self {
+            file_sources,
+            file_sizes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpStatusErrorDetail {
+    pub url: String,
+    pub status: u32,
+}
+
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub file_sources: Vec<HttpStatusErrorDetail>,
+}
+impl Display for HttpStatusError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for HttpStatusError {}
+
+impl From<Vec<HttpStatusErrorDetail>> for HttpStatusError {
+    fn from(file_sources: Vec<HttpStatusErrorDetail>) -> Self {
         Self { file_sources }
     }
 }
 
+/// A TLS handshake or certificate failure, or an unusable TLS setting.
+#[derive(Debug)]
+pub struct TlsError {
+    pub url: String,
+    pub message: String,
+}
+impl Display for TlsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for TlsError {}
+
+/// Targets which already exist with [`crate::ExistingFilePolicy::Error`].
+#[derive(Debug)]
+pub struct FileExistsError {
+    pub targets: Vec<std::path::PathBuf>,
+}
+impl Display for FileExistsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for FileExistsError {}
+
+impl From<Vec<std::path::PathBuf>> for FileExistsError {
+    fn from(targets: Vec<std::path::PathBuf>) -> Self {
+        Self { targets }
+    }
+}
+
+/// Targets given several times in a [`crate::DownloadBuilder`] with another
+/// source or other settings, found before any transfer.
+#[derive(Debug)]
+pub struct ConflictingTargetsError {
+    pub targets: Vec<std::path::PathBuf>,
+}
+impl Display for ConflictingTargetsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for ConflictingTargetsError {}
+
+impl From<Vec<std::path::PathBuf>> for ConflictingTargetsError {
+    fn from(targets: Vec<std::path::PathBuf>) -> Self {
+        Self { targets }
+    }
+}
+
+/// The response of `url` is larger than the max file size, either by its
+/// `Content-Length` or by the bytes received.
+#[derive(Debug)]
+pub struct FileTooLargeError {
+    pub url: String,
+    pub max_file_size: u64,
+}
+impl Display for FileTooLargeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for FileTooLargeError {}
+
+/// The files of a download do not fit in the free space of the file system
+/// of `dir`.
+#[derive(Debug)]
+pub struct InsufficientSpaceError {
+    pub dir: std::path::PathBuf,
+    pub required: u64,
+    pub available: u64,
+}
+impl Display for InsufficientSpaceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for InsufficientSpaceError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UnsafePathReason {
+    /// The target has no file name inside the folder.
+    Empty,
+    /// The target has a `..` component.
+    ParentDir,
+    /// The target is still absolute, or has a drive prefix, once the folder
+    /// or the root is stripped.
+    Absolute,
+    /// This directory of the target inside the folder is a symbolic link.
+    SymlinkedParent(std::path::PathBuf),
+}
+
+/// A target rejected by [`crate::DownloadFolder::add_file`] as it could be
+/// written outside of the folder.
+#[derive(Debug)]
+pub struct UnsafePathError {
+    pub folder: std::path::PathBuf,
+    pub target: std::path::PathBuf,
+    pub reason: UnsafePathReason,
+}
+impl Display for UnsafePathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for UnsafePathError {}
+
+/// Why an archive could not be extracted.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExtractErrorReason {
+    /// This entry would be written outside of the target directory.
+    UnsafeEntry(std::path::PathBuf, UnsafePathReason),
+    /// The archive has more entries than [`crate::Extract::max_entries`].
+    TooManyEntries(u64),
+    /// The extracted files are larger than [`crate::Extract::max_size`].
+    TooLarge(u64),
+}
+
+/// The archive of `url` was rejected while it was extracted, its target is
+/// left untouched.
+#[derive(Debug)]
+pub struct ExtractError {
+    pub url: String,
+    pub reason: ExtractErrorReason,
+}
+impl Display for ExtractError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(self, f)
+    }
+}
+impl Error for ExtractError {}
+
 #[derive(Clone)]
 pub struct ThreadSafeError {
     pub message: String,
@@ -60,35 +228,42 @@ impl<T: Into<String>> From<T> for ThreadSafeError {
     }
 }
 
+#[cfg(feature = "curl")]
 #[derive(Debug)]
 pub enum CurlError {
     CurlError(curl::Error),
     CurlMultiError(curl::MultiError),
     ThreadSafeError(ThreadSafeError),
 }
+#[cfg(feature = "curl")]
 impl Display for CurlError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         Debug::fmt(&self, f)
     }
 }
 
+#[cfg(feature = "curl")]
 impl Error for CurlError {}
 
+#[cfg(feature = "curl")]
 impl From<curl::Error> for CurlError {
     fn from(error: curl::Error) -> Self {
         Self::CurlError(error)
     }
 }
+#[cfg(feature = "curl")]
 impl From<curl::MultiError> for CurlError {
     fn from(error: curl::MultiError) -> Self {
         Self::CurlMultiError(error)
     }
 }
+#[cfg(feature = "curl")]
 impl From<ThreadSafeError> for CurlError {
     fn from(error: ThreadSafeError) -> Self {
         Self::ThreadSafeError(error)
     }
 }
+#[cfg(feature = "curl")]
 impl From<CurlError> for ThreadSafeError {
     fn from(error: CurlError) -> Self {
         Self {
@@ -97,11 +272,61 @@ impl From<CurlError> for ThreadSafeError {
     }
 }
 
+#[cfg(feature = "hyper")]
+#[derive(Debug)]
+pub enum HyperError {
+    HttpError(hyper::http::Error),
+    BodyError(hyper::Error),
+    ClientError(hyper_util::client::legacy::Error),
+}
+#[cfg(feature = "hyper")]
+impl Display for HyperError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Debug::fmt(&self, f)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl Error for HyperError {}
+
+#[cfg(feature = "hyper")]
+impl From<hyper::http::Error> for HyperError {
+    fn from(error: hyper::http::Error) -> Self {
+        Self::HttpError(error)
+    }
+}
+#[cfg(feature = "hyper")]
+impl From<hyper::Error> for HyperError {
+    fn from(error: hyper::Error) -> Self {
+        Self::BodyError(error)
+    }
+}
+#[cfg(feature = "hyper")]
+impl From<hyper_util::client::legacy::Error> for HyperError {
+    fn from(error: hyper_util::client::legacy::Error) -> Self {
+        Self::ClientError(error)
+    }
+}
+
 #[derive(Debug)]
 pub enum DlError {
     BadCheckSumError(BadCheckSumError),
+    HttpStatusError(HttpStatusError),
+    #[cfg(feature = "curl")]
     CurlError(CurlError),
+    #[cfg(feature = "hyper")]
+    HyperError(HyperError),
     IoError(io::Error),
+    UrlError(url::ParseError),
+    /// An [`crate::auth::AuthProvider`] could not provide credentials.
+    AuthError(ThreadSafeError),
+    TlsError(TlsError),
+    FileExistsError(FileExistsError),
+    ConflictingTargetsError(ConflictingTargetsError),
+    UnsafePathError(UnsafePathError),
+    InsufficientSpaceError(InsufficientSpaceError),
+    FileTooLargeError(FileTooLargeError),
+    ExtractError(ExtractError),
 }
 impl Display for DlError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
@@ -115,26 +340,80 @@ impl From<BadCheckSumError> for DlError {
         Self::BadCheckSumError(error)
     }
 }
+impl From<HttpStatusError> for DlError {
+    fn from(error: HttpStatusError) -> Self {
+        Self::HttpStatusError(error)
+    }
+}
+#[cfg(feature = "curl")]
 impl From<CurlError> for DlError {
     fn from(error: CurlError) -> Self {
         Self::CurlError(error)
     }
 }
+#[cfg(feature = "curl")]
 impl From<curl::Error> for DlError {
     fn from(error: curl::Error) -> Self {
         Self::CurlError(error.into())
     }
 }
+#[cfg(feature = "curl")]
 impl From<curl::MultiError> for DlError {
     fn from(error: curl::MultiError) -> Self {
         Self::CurlError(error.into())
     }
 }
+#[cfg(feature = "hyper")]
+impl From<HyperError> for DlError {
+    fn from(error: HyperError) -> Self {
+        Self::HyperError(error)
+    }
+}
 impl From<io::Error> for DlError {
     fn from(error: io::Error) -> Self {
         Self::IoError(error)
     }
 }
+impl From<TlsError> for DlError {
+    fn from(error: TlsError) -> Self {
+        Self::TlsError(error)
+    }
+}
+impl From<FileExistsError> for DlError {
+    fn from(error: FileExistsError) -> Self {
+        Self::FileExistsError(error)
+    }
+}
+impl From<ConflictingTargetsError> for DlError {
+    fn from(error: ConflictingTargetsError) -> Self {
+        Self::ConflictingTargetsError(error)
+    }
+}
+impl From<UnsafePathError> for DlError {
+    fn from(error: UnsafePathError) -> Self {
+        Self::UnsafePathError(error)
+    }
+}
+impl From<InsufficientSpaceError> for DlError {
+    fn from(error: InsufficientSpaceError) -> Self {
+        Self::InsufficientSpaceError(error)
+    }
+}
+impl From<FileTooLargeError> for DlError {
+    fn from(error: FileTooLargeError) -> Self {
+        Self::FileTooLargeError(error)
+    }
+}
+impl From<ExtractError> for DlError {
+    fn from(error: ExtractError) -> Self {
+        Self::ExtractError(error)
+    }
+}
+impl From<url::ParseError> for DlError {
+    fn from(error: url::ParseError) -> Self {
+        Self::UrlError(error)
+    }
+}
 
 #[derive(Debug)]
 pub enum ThreadSafeDlError {
@@ -213,6 +492,7 @@ impl BinaryReprError {
 pub enum CheckHashError {
     IoError(std::io::Error),
     HashError(BadCheckSumErrorDetail),
+    SizeError(BadSizeErrorDetail),
 }
 
 impl From<std::io::Error> for CheckHashError {
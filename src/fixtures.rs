@@ -0,0 +1,38 @@
+//! Files and settings shared by the unit tests.
+use crate::hash::{BinaryRepr, BinaryReprFormat};
+use crate::http_client::{CheckSum, DownloadConfig, FileToDl};
+use crate::transport::mock::MockTransport;
+use std::path::Path;
+use std::sync::Arc;
+
+/// MD5 of `hello`.
+pub const HELLO_MD5: &str = "5d41402abc4b2a76b9719d911017c592";
+
+pub fn md5(hex: &str) -> CheckSum {
+    CheckSum::Md5(BinaryRepr::new(hex, BinaryReprFormat::Hex).unwrap())
+}
+
+/// `name` in `dir` downloaded from `https://example.com/<name>`, `dir` may be
+/// empty for a relative target.
+pub fn file(dir: impl AsRef<Path>, name: &str, check_sum: CheckSum) -> FileToDl {
+    FileToDl {
+        target: dir.as_ref().join(name).into_os_string().into(),
+        source: format!("https://example.com/{}", name),
+        check_sum,
+        ..FileToDl::default()
+    }
+}
+
+pub fn config(transport: &MockTransport) -> DownloadConfig {
+    DownloadConfig {
+        transport: Arc::new(transport.clone()),
+        ..DownloadConfig::default()
+    }
+}
+
+/// A temporary directory and the path of `name` in it.
+pub fn temp_target(name: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join(name);
+    (dir, target)
+}
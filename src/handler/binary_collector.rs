@@ -1,14 +1,24 @@
+#[cfg(feature = "curl")]
 use curl::easy::{Easy2, Handler};
 use std::borrow::Cow;
+use std::io::{self, Write};
 
-#[derive(Default)]
+/// Collects a body in memory.
+#[derive(Default, Clone, PartialEq, Eq)]
 pub struct BinaryCollector(Vec<u8>);
 
+impl BinaryCollector {
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
 impl<'a> std::convert::From<&'a BinaryCollector> for Cow<'a, str> {
-    fn from(value: &BinaryCollector) -> Cow<str> {
+    fn from(value: &BinaryCollector) -> Cow<'_, str> {
         String::from_utf8_lossy(&value.0)
     }
 }
+#[cfg(feature = "curl")]
 impl Handler for BinaryCollector {
     fn write(&mut self, data: &[u8]) -> Result<usize, curl::easy::WriteError> {
         self.0.extend_from_slice(data);
@@ -16,12 +26,24 @@ impl Handler for BinaryCollector {
     }
 }
 
+#[cfg(feature = "curl")]
 impl From<BinaryCollector> for Easy2<BinaryCollector> {
     fn from(c: BinaryCollector) -> Self {
         Self::new(c)
     }
 }
 
+impl Write for BinaryCollector {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 impl AsRef<[u8]> for BinaryCollector {
     fn as_ref(&self) -> &[u8] {
         self.0.as_ref()
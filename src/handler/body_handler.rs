@@ -0,0 +1,57 @@
+use crate::http_client::FileToDl;
+use std::io::{self, Write};
+
+/// Receives the body of a file instead of its target, see [`HandlerFactory`].
+///
+/// The body is written as it is received, decoded when the file is
+/// decompressed. A handler dropped without [`BodyHandler::commit`] belongs to
+/// a failed download and must discard what it received.
+pub trait BodyHandler: Write + Send {
+    /// Called once the whole body passed the status, size and checksum
+    /// checks, where a file would be renamed to its target.
+    fn commit(self: Box<Self>) -> io::Result<()>;
+}
+
+/// Creates the [`BodyHandler`] of each downloaded file, for sinks other than
+/// the local file system: a database blob, an encrypting writer...
+///
+/// ```
+/// use file_download::handler::{BodyHandler, HandlerFactory};
+/// use file_download::FileToDl;
+/// use std::io::{self, Write};
+/// use std::sync::{Arc, Mutex};
+///
+/// #[derive(Default)]
+/// struct Blobs(Arc<Mutex<Vec<(String, Vec<u8>)>>>);
+///
+/// struct Blob(String, Vec<u8>, Arc<Mutex<Vec<(String, Vec<u8>)>>>);
+///
+/// impl Write for Blob {
+///     fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+///         self.1.write(data)
+///     }
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// impl BodyHandler for Blob {
+///     fn commit(self: Box<Self>) -> io::Result<()> {
+///         self.2.lock().unwrap().push((self.0, self.1));
+///         Ok(())
+///     }
+/// }
+///
+/// impl HandlerFactory for Blobs {
+///     fn create(&self, file: &FileToDl) -> io::Result<Box<dyn BodyHandler>> {
+///         let key = file.target.to_string_lossy().into_owned();
+///         Ok(Box::new(Blob(key, Vec::new(), self.0.clone())))
+///     }
+/// }
+/// ```
+pub trait HandlerFactory: Send + Sync {
+    /// The handler of `file`, called once its successful response is
+    /// received. Its writes and its commit run on the blocking thread pool of
+    /// the runtime, so they may block.
+    fn create(&self, file: &FileToDl) -> io::Result<Box<dyn BodyHandler>>;
+}
@@ -0,0 +1,190 @@
+#[cfg(all(feature = "curl", feature = "decompression"))]
+use curl::easy::{Easy2, Handler, WriteError};
+#[cfg(feature = "decompression")]
+use std::io::{self, Write};
+
+/// Compression of a downloaded file, decoded while it is written.
+///
+/// Decoding needs the `decompression` feature, downloads of compressed files
+/// fail with an I/O error without it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+#[cfg(feature = "decompression")]
+enum Decoder<W: Write> {
+    Gzip(flate2::write::MultiGzDecoder<W>),
+    Zstd(zstd::stream::zio::Writer<W, zstd::stream::raw::Decoder<'static>>),
+    Xz(xz2::write::XzDecoder<W>),
+    Bzip2(bzip2::write::BzDecoder<W>),
+}
+
+/// Writes the decoded stream of a compressed payload into `W`.
+///
+/// The stream must be ended with [`Decompressor::finish`], which fails when
+/// it is truncated.
+#[cfg(feature = "decompression")]
+pub struct Decompressor<W: Write> {
+    decoder: Decoder<W>,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "decompression")]
+impl<W: Write> Decompressor<W> {
+    pub fn new(compression: Compression, inner: W) -> io::Result<Self> {
+        let decoder = match compression {
+            Compression::Gzip => Decoder::Gzip(flate2::write::MultiGzDecoder::new(inner)),
+            Compression::Zstd => Decoder::Zstd(zstd::stream::zio::Writer::new(
+                inner,
+                zstd::stream::raw::Decoder::new()?,
+            )),
+            Compression::Xz => Decoder::Xz(xz2::write::XzDecoder::new_multi_decoder(inner)),
+            Compression::Bzip2 => Decoder::Bzip2(bzip2::write::BzDecoder::new(inner)),
+        };
+        Ok(Self {
+            decoder,
+            error: None,
+        })
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        match &mut self.decoder {
+            Decoder::Gzip(decoder) => decoder.get_mut(),
+            Decoder::Zstd(decoder) => decoder.writer_mut(),
+            Decoder::Xz(decoder) => decoder.get_mut(),
+            Decoder::Bzip2(decoder) => decoder.get_mut(),
+        }
+    }
+
+    /// Writes the end of the decoded stream and returns `W`, fails when the
+    /// compressed stream is incomplete or corrupted.
+    pub fn finish(self) -> io::Result<W> {
+        match self.decoder {
+            Decoder::Gzip(decoder) => decoder.finish(),
+            Decoder::Zstd(mut decoder) => {
+                decoder.finish()?;
+                Ok(decoder.into_inner().0)
+            }
+            Decoder::Xz(mut decoder) => decoder.finish(),
+            Decoder::Bzip2(mut decoder) => decoder.finish(),
+        }
+    }
+
+    /// The error which aborted a curl transfer.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+#[cfg(feature = "decompression")]
+impl<W: Write> Write for Decompressor<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match &mut self.decoder {
+            Decoder::Gzip(decoder) => decoder.write(data),
+            Decoder::Zstd(decoder) => decoder.write(data),
+            Decoder::Xz(decoder) => decoder.write(data),
+            Decoder::Bzip2(decoder) => decoder.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.decoder {
+            Decoder::Gzip(decoder) => decoder.flush(),
+            Decoder::Zstd(decoder) => decoder.flush(),
+            Decoder::Xz(decoder) => decoder.flush(),
+            Decoder::Bzip2(decoder) => decoder.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "decompression")]
+impl<W: Write> std::fmt::Debug for Decompressor<W> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let compression = match &self.decoder {
+            Decoder::Gzip(_) => Compression::Gzip,
+            Decoder::Zstd(_) => Compression::Zstd,
+            Decoder::Xz(_) => Compression::Xz,
+            Decoder::Bzip2(_) => Compression::Bzip2,
+        };
+        write!(fmt, "Decompressor({:?})", compression)
+    }
+}
+
+#[cfg(all(feature = "curl", feature = "decompression"))]
+impl<W: Write> Handler for Decompressor<W> {
+    /// Failures are kept for [`Decompressor::take_error`], the short count
+    /// makes curl abort the transfer with a write error.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        match self.write_all(data) {
+            Ok(()) => Ok(data.len()),
+            Err(err) => {
+                self.error = Some(err);
+                Ok(0)
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "curl", feature = "decompression"))]
+impl<W: Write> From<Decompressor<W>> for Easy2<Decompressor<W>> {
+    fn from(d: Decompressor<W>) -> Self {
+        Self::new(d)
+    }
+}
+
+#[cfg(all(test, feature = "decompression"))]
+mod test {
+    use super::*;
+
+    fn decode(compression: Compression, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decompressor = Decompressor::new(compression, Vec::new())?;
+        // small writes, like the chunks of a transfer
+        for chunk in data.chunks(7) {
+            decompressor.write_all(chunk)?;
+        }
+        decompressor.finish()
+    }
+
+    fn compress(compression: Compression, data: &[u8]) -> Vec<u8> {
+        match compression {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    #[test]
+    fn test_decodes_every_format() {
+        let data = b"hello hello hello hello hello".repeat(100);
+        for compression in [
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Xz,
+            Compression::Bzip2,
+        ] {
+            let compressed = compress(compression, &data);
+            assert_eq!(decode(compression, &compressed).unwrap(), data);
+            let truncated = &compressed[..compressed.len() / 2];
+            assert!(decode(compression, truncated).is_err(), "{:?}", compression);
+        }
+    }
+}
@@ -1,40 +1,499 @@
+#[cfg(feature = "decompression")]
+use super::Decompressor;
+use super::{BinaryCollector, Compression, Tee, TeeFailure};
+use crate::transport::Response;
+#[cfg(feature = "curl")]
 use curl::easy::{self, Easy2, Handler};
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub struct FileCollector {
     path: PathBuf,
-    file: Option<File>,
+    copies: Vec<PathBuf>,
+    output: Option<Output>,
+    response: Response,
+    error: Option<io::Error>,
+    size_hint: Option<u64>,
+    size_limit: Option<u64>,
+    max_file_size: Option<u64>,
+    decompression: Option<Compression>,
+    body_md5: Option<BodyMd5>,
+    in_memory: bool,
+}
+
+/// The error of [`FileCollector::write_data`] once the body exceeds the size
+/// limit, transports end the transfer and keep the response.
+#[derive(Debug)]
+pub struct SizeLimitExceeded {
+    pub limit: u64,
+}
+
+impl std::fmt::Display for SizeLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "body larger than {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for SizeLimitExceeded {}
+
+/// The error of [`FileCollector::write_data`] for data past the max file
+/// size, transports fail the transfer.
+#[derive(Debug)]
+pub struct MaxFileSizeExceeded {
+    pub max_file_size: u64,
+}
+
+impl std::fmt::Display for MaxFileSizeExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "file larger than {} bytes", self.max_file_size)
+    }
+}
+
+impl std::error::Error for MaxFileSizeExceeded {}
+
+/// Whether `err` is a [`SizeLimitExceeded`].
+pub(crate) fn is_size_limit(err: &io::Error) -> bool {
+    err.get_ref()
+        .is_some_and(|err| err.is::<SizeLimitExceeded>())
+}
+
+/// `err` prefixed with `path`, the size limit errors are kept as is so
+/// transports still recognize them.
+fn with_path(path: &Path, err: io::Error) -> io::Error {
+    if path.as_os_str().is_empty()
+        || err
+            .get_ref()
+            .is_some_and(|err| err.is::<SizeLimitExceeded>() || err.is::<MaxFileSizeExceeded>())
+    {
+        return err;
+    }
+    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+#[derive(Debug)]
+enum Sink {
+    File(File),
+    Files(Tee<File>),
+    Memory(BinaryCollector),
+}
+
+impl Write for Sink {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.write(data),
+            Self::Files(files) => Write::write(files, data),
+            Self::Memory(body) => Write::write(body, data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.flush(),
+            Self::Files(files) => files.flush(),
+            Self::Memory(body) => body.flush(),
+        }
+    }
+}
+
+/// The opened file or the body in memory, the limits count the bytes written
+/// to it so they apply to the decoded bytes of a compressed payload.
+#[derive(Debug)]
+struct Target {
+    sink: Sink,
+    size_limit: Option<u64>,
+    max_file_size: Option<u64>,
+    written: u64,
+}
+
+impl Write for Target {
+    /// The chunk crossing the size limit is still written so the file shows
+    /// that it is too large.
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Some(max_file_size) = self.max_file_size {
+            if self.written + data.len() as u64 > max_file_size {
+                return Err(io::Error::other(MaxFileSizeExceeded { max_file_size }));
+            }
+        }
+        self.sink.write_all(data)?;
+        self.written += data.len() as u64;
+        match self.size_limit {
+            Some(limit) if self.written > limit => {
+                Err(io::Error::other(SizeLimitExceeded { limit }))
+            }
+            _ => Ok(data.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+}
+
+#[derive(Debug)]
+enum Output {
+    Plain(Target),
+    #[cfg(feature = "decompression")]
+    Decoded(Box<Decompressor<Target>>),
+}
+
+impl Write for Output {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(target) => target.write(data),
+            #[cfg(feature = "decompression")]
+            Self::Decoded(decompressor) => Write::write(decompressor, data),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(target) => target.flush(),
+            #[cfg(feature = "decompression")]
+            Self::Decoded(decompressor) => decompressor.flush(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct BodyMd5(md5::Context);
+
+impl std::fmt::Debug for BodyMd5 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "BodyMd5")
+    }
 }
 
 impl<P: Into<PathBuf>> From<P> for FileCollector {
     fn from(path: P) -> Self {
         Self {
             path: path.into(),
-            file: None,
+            copies: Vec::new(),
+            output: None,
+            response: Response::default(),
+            error: None,
+            size_hint: None,
+            size_limit: None,
+            max_file_size: None,
+            decompression: None,
+            body_md5: None,
+            in_memory: false,
         }
     }
 }
 
-impl Handler for FileCollector {
-    fn write(&mut self, data: &[u8]) -> Result<usize, easy::WriteError> {
-        let path = self.path.as_os_str();
-        let file = self.file.get_or_insert_with(|| {
+impl FileCollector {
+    /// Also writes the file to `path`, the transfer fails when one of the
+    /// copies cannot be written.
+    pub fn add_copy<P: Into<PathBuf>>(&mut self, path: P) {
+        self.copies.push(path.into());
+    }
+
+    /// Expected size of the file, it is preallocated when created.
+    pub fn set_size_hint(&mut self, len: u64) {
+        self.size_hint = Some(len);
+    }
+
+    /// Largest accepted body, more data fails with [`SizeLimitExceeded`].
+    pub fn set_size_limit(&mut self, limit: u64) {
+        self.size_limit = Some(limit);
+    }
+
+    /// Largest file written, data past it is dropped and fails with
+    /// [`MaxFileSizeExceeded`].
+    pub fn set_max_file_size(&mut self, max_file_size: u64) {
+        self.max_file_size = Some(max_file_size);
+    }
+
+    /// Decodes the body before writing it, the limits then apply to the
+    /// decoded file and the MD5 of the received bytes is computed, see
+    /// [`FileCollector::body_md5`].
+    pub fn set_decompression(&mut self, compression: Compression) {
+        self.decompression = Some(compression);
+        self.body_md5 = Some(BodyMd5(md5::Context::new()));
+    }
+
+    /// MD5 of the bytes received so far, before decoding, only computed with
+    /// a decompression.
+    pub fn body_md5(&self) -> Option<[u8; 16]> {
+        self.body_md5.clone().map(|context| context.0.compute().0)
+    }
+
+    /// Keeps the body in memory instead of writing the file, see
+    /// [`FileCollector::take_body`].
+    pub fn set_in_memory(&mut self) {
+        self.in_memory = true;
+    }
+
+    /// The body received so far when it is kept in memory, the collector
+    /// is left empty.
+    pub fn take_body(&mut self) -> Option<BinaryCollector> {
+        if !self.in_memory {
+            return None;
+        }
+        let target = match &mut self.output {
+            None => return Some(BinaryCollector::default()),
+            Some(Output::Plain(target)) => target,
+            #[cfg(feature = "decompression")]
+            Some(Output::Decoded(decompressor)) => decompressor.get_mut(),
+        };
+        match &mut target.sink {
+            Sink::Memory(body) => Some(std::mem::take(body)),
+            Sink::File(_) | Sink::Files(_) => None,
+        }
+    }
+
+    fn open(&self) -> io::Result<Output> {
+        let create = |path: &Path| {
             fs::OpenOptions::new()
                 .create(true)
                 .truncate(true)
                 .write(true)
                 .open(path)
-                .expect("file created")
-        });
-        file.write(data).map_err(|_| easy::WriteError::Pause)
+        };
+        let sink = if self.in_memory {
+            Sink::Memory(BinaryCollector::default())
+        } else if self.copies.is_empty() {
+            Sink::File(create(&self.path)?)
+        } else {
+            let mut files = Tee::default();
+            for path in std::iter::once(&self.path).chain(self.copies.iter()) {
+                files.push(create(path)?, TeeFailure::Abort);
+            }
+            Sink::Files(files)
+        };
+        let target = Target {
+            sink,
+            size_limit: self.size_limit,
+            max_file_size: self.max_file_size,
+            written: 0,
+        };
+        match self.decompression {
+            None => {
+                let size_hint = self.size_hint.or_else(|| {
+                    self.response
+                        .header("Content-Length")
+                        .and_then(|len| len.parse().ok())
+                });
+                let limit = self.size_limit.into_iter().chain(self.max_file_size).min();
+                if let Some(len) = size_hint.map(|len| limit.map_or(len, |limit| len.min(limit))) {
+                    match &target.sink {
+                        Sink::File(file) => preallocate(file, len)?,
+                        Sink::Files(files) => {
+                            for file in files.iter() {
+                                preallocate(file, len)?;
+                            }
+                        }
+                        Sink::Memory(_) => (),
+                    }
+                }
+                Ok(Output::Plain(target))
+            }
+            #[cfg(feature = "decompression")]
+            Some(compression) => Ok(Output::Decoded(Box::new(Decompressor::new(
+                compression,
+                target,
+            )?))),
+            #[cfg(not(feature = "decompression"))]
+            Some(compression) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "decoding {:?} needs the `decompression` feature",
+                    compression
+                ),
+            )),
+        }
+    }
+
+    /// Appends `data` to the file and its copies, they are created (or
+    /// truncated) on the first call.
+    ///
+    /// The file is preallocated from the size hint, or else the
+    /// `Content-Length` received by curl, at most the limits, unless the
+    /// body is decoded. Errors name the file.
+    pub fn write_data(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Some(context) = &mut self.body_md5 {
+            context.0.consume(data);
+        }
+        let output = match &mut self.output {
+            Some(output) => output,
+            None => {
+                let output = self.open().map_err(|err| with_path(&self.path, err))?;
+                self.output.insert(output)
+            }
+        };
+        output
+            .write_all(data)
+            .map_err(|err| with_path(&self.path, err))?;
+        Ok(data.len())
+    }
+
+    /// Ends the body once the transfer is complete, a decoded body fails when
+    /// it is truncated.
+    pub fn finish(&mut self) -> io::Result<()> {
+        #[cfg(feature = "decompression")]
+        {
+            self.output = match self.output.take() {
+                Some(Output::Decoded(decompressor)) => Some(Output::Plain(
+                    decompressor
+                        .finish()
+                        .map_err(|err| with_path(&self.path, err))?,
+                )),
+                output => output,
+            };
+        }
+        Ok(())
+    }
+
+    /// The error which aborted a curl transfer, see [`FileCollector::write_data`].
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Response headers received so far, only filled by curl.
+    pub fn response(&self) -> &Response {
+        &self.response
+    }
+}
+
+/// Reserves `len` bytes for `file` without changing its size, a no-op where
+/// `fallocate` is not supported.
+#[cfg(target_os = "linux")]
+fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    use rustix::fs::{fallocate, FallocateFlags};
+    use rustix::io::Errno;
+    if len == 0 {
+        return Ok(());
     }
+    match fallocate(file, FallocateFlags::KEEP_SIZE, 0, len) {
+        Err(Errno::OPNOTSUPP | Errno::NOSYS) => Ok(()),
+        result => result.map_err(io::Error::from),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn preallocate(_file: &File, _len: u64) -> io::Result<()> {
+    Ok(())
 }
 
+#[cfg(feature = "curl")]
+impl Handler for FileCollector {
+    /// Failures are kept for [`FileCollector::take_error`], the short count
+    /// makes curl abort the transfer with a write error.
+    fn write(&mut self, data: &[u8]) -> Result<usize, easy::WriteError> {
+        match self.write_data(data) {
+            Ok(len) => Ok(len),
+            Err(err) => {
+                self.error = Some(err);
+                Ok(0)
+            }
+        }
+    }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        self.response.push_header_line(data);
+        true
+    }
+}
+
+#[cfg(feature = "curl")]
 impl From<FileCollector> for Easy2<FileCollector> {
     fn from(c: FileCollector) -> Self {
         Self::new(c)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::temp_target;
+
+    #[test]
+    fn test_write_errors_are_kept() {
+        let (_dir, target) = temp_target("missing/a.txt");
+        let mut collector = FileCollector::from(&target);
+
+        let err = collector.write_data(b"hello").unwrap_err();
+        assert!(err.to_string().contains("a.txt"));
+
+        #[cfg(feature = "curl")]
+        {
+            assert_eq!(Handler::write(&mut collector, b"hello").unwrap(), 0);
+            assert!(collector.take_error().is_some());
+            assert!(collector.take_error().is_none());
+        }
+    }
+
+    #[test]
+    fn test_preallocation_keeps_the_written_size() {
+        let (_dir, target) = temp_target("a.txt");
+        let mut collector = FileCollector::from(&target);
+        collector.set_size_hint(1 << 20);
+
+        collector.write_data(b"hello").unwrap();
+        drop(collector);
+
+        assert_eq!(std::fs::read(&target).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_copies_receive_the_body() {
+        let (dir, target) = temp_target("a.txt");
+        let mut collector = FileCollector::from(&target);
+        collector.add_copy(dir.path().join("b.txt"));
+        collector.set_size_hint(1 << 20);
+
+        collector.write_data(b"hello").unwrap();
+        drop(collector);
+
+        assert_eq!(std::fs::read(&target).unwrap(), b"hello");
+        assert_eq!(std::fs::read(dir.path().join("b.txt")).unwrap(), b"hello");
+
+        let mut collector = FileCollector::from(&target);
+        collector.add_copy(dir.path().join("missing/b.txt"));
+        assert!(collector.write_data(b"hello").is_err());
+    }
+
+    #[test]
+    fn test_size_limit_stops_the_transfer() {
+        let (_dir, target) = temp_target("a.txt");
+        let mut collector = FileCollector::from(&target);
+        collector.set_size_limit(8);
+
+        collector.write_data(b"hello").unwrap();
+        let err = collector.write_data(b"hello").unwrap_err();
+        assert!(is_size_limit(&err));
+        drop(collector);
+
+        assert_eq!(std::fs::read(&target).unwrap(), b"hellohello");
+    }
+
+    #[test]
+    fn test_in_memory_body_is_kept() {
+        let mut collector = FileCollector::from("");
+        collector.set_in_memory();
+        assert_eq!(collector.take_body().unwrap().as_ref(), b"");
+        collector.set_size_limit(8);
+
+        collector.write_data(b"hello").unwrap();
+        assert!(is_size_limit(&collector.write_data(b"hello").unwrap_err()));
+        collector.finish().unwrap();
+
+        assert_eq!(collector.take_body().unwrap().as_ref(), b"hellohello");
+    }
+
+    #[test]
+    fn test_max_file_size_drops_the_overflow() {
+        let (_dir, target) = temp_target("a.txt");
+        let mut collector = FileCollector::from(&target);
+        collector.set_max_file_size(8);
+
+        collector.write_data(b"hello").unwrap();
+        let err = collector.write_data(b"hello").unwrap_err();
+        assert!(err.get_ref().unwrap().is::<MaxFileSizeExceeded>());
+        drop(collector);
+
+        assert_eq!(std::fs::read(&target).unwrap(), b"hello");
+    }
+}
@@ -1,4 +1,17 @@
 mod binary_collector;
+mod body_handler;
+mod decompressor;
 mod file_collector;
+#[cfg(feature = "curl")]
+mod stream_collector;
+mod tee;
 pub use binary_collector::BinaryCollector;
-pub use file_collector::FileCollector;
+pub use body_handler::{BodyHandler, HandlerFactory};
+pub use decompressor::Compression;
+#[cfg(feature = "decompression")]
+pub use decompressor::Decompressor;
+pub(crate) use file_collector::is_size_limit;
+pub use file_collector::{FileCollector, MaxFileSizeExceeded, SizeLimitExceeded};
+#[cfg(feature = "curl")]
+pub use stream_collector::StreamCollector;
+pub use tee::{Tee, TeeFailure};
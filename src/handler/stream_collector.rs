@@ -0,0 +1,156 @@
+use crate::error::DlError;
+use crate::transport::Response;
+use bytes::Bytes;
+use curl::easy::{Easy2, Handler, WriteError};
+use futures::channel::{mpsc, oneshot};
+use futures::executor::block_on;
+use futures::task::{waker, ArcWake};
+use futures::SinkExt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread::{self, Thread};
+
+/// Sends the body of a curl transfer through a bounded channel, the
+/// transfer is paused while the channel is full instead of being buffered.
+///
+/// The response is sent with the first chunk of the body, or once the
+/// transfer is finished. The transfer must be driven by a `Multi` handle,
+/// resumed once [`StreamCollector::wait_ready`] returns.
+pub struct StreamCollector {
+    response: Response,
+    head: Option<oneshot::Sender<Result<Response, DlError>>>,
+    sender: mpsc::Sender<Result<Bytes, DlError>>,
+    paused: bool,
+}
+
+struct Unpark(Thread);
+
+impl ArcWake for Unpark {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.unpark();
+    }
+}
+
+impl StreamCollector {
+    pub fn new(
+        head: oneshot::Sender<Result<Response, DlError>>,
+        sender: mpsc::Sender<Result<Bytes, DlError>>,
+    ) -> Self {
+        Self {
+            response: Response::default(),
+            head: Some(head),
+            sender,
+            paused: false,
+        }
+    }
+
+    /// Whether the last chunk paused the transfer as the channel was full.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Blocks the thread until the channel has room for a chunk, `false`
+    /// when the receiver is dropped.
+    pub fn wait_ready(&mut self) -> bool {
+        let waker = waker(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match self.sender.poll_ready(&mut cx) {
+                Poll::Ready(ready) => {
+                    self.paused = false;
+                    return ready.is_ok();
+                }
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn send_head(&mut self) {
+        if let Some(head) = self.head.take() {
+            let _ = head.send(Ok(self.response.clone()));
+        }
+    }
+
+    /// Ends the stream with the result of the transfer, a failure before the
+    /// body is the one of the response.
+    pub fn finish(&mut self, result: Result<(), DlError>) {
+        match (self.head.take(), result) {
+            (Some(head), result) => {
+                let _ = head.send(result.map(|()| self.response.clone()));
+            }
+            (None, Err(err)) => {
+                let _ = block_on(self.sender.send(Err(err)));
+            }
+            (None, Ok(())) => {}
+        }
+        self.sender.close_channel();
+    }
+}
+
+impl Handler for StreamCollector {
+    /// A full channel pauses the transfer, curl delivers the chunk again once
+    /// resumed. A dropped receiver aborts it.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.send_head();
+        match self.sender.try_send(Ok(Bytes::copy_from_slice(data))) {
+            Ok(()) => Ok(data.len()),
+            Err(err) if err.is_full() => {
+                self.paused = true;
+                Err(WriteError::Pause)
+            }
+            Err(_) => Ok(0),
+        }
+    }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        self.response.push_header_line(data);
+        true
+    }
+}
+
+impl From<StreamCollector> for Easy2<StreamCollector> {
+    fn from(c: StreamCollector) -> Self {
+        Self::new(c)
+    }
+}
+
+impl std::fmt::Debug for StreamCollector {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "StreamCollector({})", self.response.status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_full_channel_pauses_the_transfer() {
+        let (head_sender, head) = oneshot::channel();
+        let (sender, mut receiver) = mpsc::channel(0);
+        let mut collector = StreamCollector::new(head_sender, sender);
+        collector.header(b"HTTP/1.1 200 OK\r\n");
+        collector.header(b"Content-Length: 10\r\n");
+        collector.header(b"\r\n");
+
+        assert_eq!(collector.write(b"hello").unwrap(), 5);
+        assert_eq!(block_on(head).unwrap().unwrap().status, 200);
+        assert!(matches!(collector.write(b"world"), Err(WriteError::Pause)));
+        assert!(collector.is_paused());
+        let consumer = thread::spawn(move || {
+            let first = block_on(receiver.next()).unwrap().unwrap();
+            (first, receiver)
+        });
+        assert!(collector.wait_ready());
+        assert_eq!(collector.write(b"world").unwrap(), 5);
+        let (first, mut receiver) = consumer.join().unwrap();
+        assert_eq!(first, "hello");
+
+        collector.finish(Ok(()));
+        let rest = block_on(receiver.by_ref().collect::<Vec<_>>());
+        assert_eq!(rest.len(), 1);
+        drop(receiver);
+        assert_eq!(collector.write(b"!").unwrap(), 0);
+    }
+}
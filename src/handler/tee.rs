@@ -0,0 +1,226 @@
+use super::BodyHandler;
+#[cfg(feature = "curl")]
+use curl::easy::{Easy2, Handler, WriteError};
+use std::io::{self, Write};
+
+/// What a [`Tee`] does when one of its sinks fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TeeFailure {
+    /// The write fails, which aborts the transfer.
+    #[default]
+    Abort,
+    /// The sink is detached and the others keep receiving the body, its
+    /// error is kept, see [`Tee::detached`].
+    Detach,
+}
+
+/// Writes the same bytes to several sinks.
+///
+/// The write fails when a sink with [`TeeFailure::Abort`] fails, or when
+/// every sink is detached.
+///
+/// ```
+/// use file_download::handler::{Tee, TeeFailure};
+/// use std::io::Write;
+///
+/// let mut tee = Tee::default();
+/// tee.push(Vec::new(), TeeFailure::Abort);
+/// tee.push(Vec::new(), TeeFailure::Detach);
+/// tee.write_all(b"hello").unwrap();
+/// let sinks: Vec<_> = tee.into_sinks().into_iter().flatten().collect();
+/// assert_eq!(sinks, [b"hello", b"hello"]);
+/// ```
+pub struct Tee<W: Write> {
+    sinks: Vec<(Option<W>, TeeFailure)>,
+    detached: Vec<(usize, io::Error)>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Default for Tee<W> {
+    fn default() -> Self {
+        Self {
+            sinks: Vec::new(),
+            detached: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl<W: Write> Tee<W> {
+    /// Adds a sink, which only receives the bytes written from now on.
+    pub fn push(&mut self, sink: W, on_failure: TeeFailure) {
+        self.sinks.push((Some(sink), on_failure));
+    }
+
+    /// The sinks still attached.
+    pub fn iter(&self) -> impl Iterator<Item = &W> {
+        self.sinks.iter().filter_map(|(sink, _)| sink.as_ref())
+    }
+
+    /// The errors of the detached sinks, with their index.
+    pub fn detached(&self) -> &[(usize, io::Error)] {
+        &self.detached
+    }
+
+    /// The sinks in the order they were added, `None` once detached.
+    pub fn into_sinks(self) -> Vec<Option<W>> {
+        self.sinks.into_iter().map(|(sink, _)| sink).collect()
+    }
+
+    /// The error which aborted a curl transfer.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Runs `op` on every attached sink, applying their failure policy.
+    fn each(&mut self, mut op: impl FnMut(&mut W) -> io::Result<()>) -> io::Result<()> {
+        for (i, (slot, on_failure)) in self.sinks.iter_mut().enumerate() {
+            let Some(sink) = slot else {
+                continue;
+            };
+            match (op(sink), on_failure) {
+                (Ok(()), _) => (),
+                (Err(err), TeeFailure::Abort) => return Err(err),
+                (Err(err), TeeFailure::Detach) => {
+                    self.detached.push((i, err));
+                    *slot = None;
+                }
+            }
+        }
+        if !self.sinks.is_empty() && self.iter().next().is_none() {
+            return Err(io::Error::other("every sink of the tee is detached"));
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.each(|sink| sink.write_all(data))?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.each(|sink| sink.flush())
+    }
+}
+
+/// Commits every attached handler, the failure of one with
+/// [`TeeFailure::Abort`] is returned once the others are committed.
+impl BodyHandler for Tee<Box<dyn BodyHandler>> {
+    fn commit(self: Box<Self>) -> io::Result<()> {
+        let mut result = Ok(());
+        for (sink, on_failure) in self.sinks {
+            let Some(sink) = sink else {
+                continue;
+            };
+            if let (Err(err), TeeFailure::Abort) = (sink.commit(), on_failure) {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(feature = "curl")]
+impl<W: Write> Handler for Tee<W> {
+    /// Failures are kept for [`Tee::take_error`], the short count makes curl
+    /// abort the transfer with a write error.
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        match self.write_all(data) {
+            Ok(()) => Ok(data.len()),
+            Err(err) => {
+                self.error = Some(err);
+                Ok(0)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "curl")]
+impl<W: Write> From<Tee<W>> for Easy2<Tee<W>> {
+    fn from(tee: Tee<W>) -> Self {
+        Self::new(tee)
+    }
+}
+
+impl<W: Write> std::fmt::Debug for Tee<W> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            fmt,
+            "Tee({} sinks, {} detached)",
+            self.sinks.len(),
+            self.detached.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _data: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl BodyHandler for Broken {
+        fn commit(self: Box<Self>) -> io::Result<()> {
+            Err(io::Error::other("broken"))
+        }
+    }
+
+    impl BodyHandler for Vec<u8> {
+        fn commit(self: Box<Self>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failure_policies() {
+        let mut tee: Tee<Box<dyn Write>> = Tee::default();
+        tee.push(Box::new(Broken), TeeFailure::Detach);
+        tee.push(Box::new(Vec::new()), TeeFailure::Abort);
+        tee.write_all(b"hello").unwrap();
+        assert_eq!(tee.detached().len(), 1);
+        assert_eq!(tee.detached()[0].0, 0);
+        assert_eq!(tee.iter().count(), 1);
+
+        tee.push(Box::new(Broken), TeeFailure::Abort);
+        assert!(tee.write_all(b"hello").is_err());
+
+        let mut tee = Tee::default();
+        tee.push(Broken, TeeFailure::Detach);
+        assert!(tee.write_all(b"hello").is_err());
+    }
+
+    #[test]
+    fn test_detached_handlers_are_not_committed() {
+        let mut tee: Tee<Box<dyn BodyHandler>> = Tee::default();
+        tee.push(Box::new(Broken), TeeFailure::Detach);
+        tee.push(Box::new(Vec::new()), TeeFailure::Abort);
+        tee.write_all(b"hello").unwrap();
+        Box::new(tee).commit().unwrap();
+
+        let mut tee: Tee<Box<dyn BodyHandler>> = Tee::default();
+        tee.push(Box::new(Vec::new()), TeeFailure::Abort);
+        tee.push(Box::new(Broken), TeeFailure::Abort);
+        assert!(Box::new(tee).commit().is_err());
+    }
+
+    #[cfg(feature = "curl")]
+    #[test]
+    fn test_curl_write_errors_are_kept() {
+        let mut tee = Tee::default();
+        tee.push(Broken, TeeFailure::Abort);
+        assert_eq!(Handler::write(&mut tee, b"hello").unwrap(), 0);
+        assert!(tee.take_error().is_some());
+    }
+}
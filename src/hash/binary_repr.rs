@@ -96,6 +96,8 @@ impl PartialEq<Self> for BinaryRepr {
     }
 }
 
+// `is_multiple_of` is not stable before Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
 fn from_bin(chars: &str) -> Result<Vec<u8>, BinaryReprError> {
     let mut res = Vec::with_capacity(chars.len() / 8 + usize::from(chars.len() % 8 != 0));
     for chunk_c in IterChunk::new(chars.as_bytes().iter().rev(), 8) {
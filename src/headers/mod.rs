@@ -0,0 +1,155 @@
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use url::Url;
+
+pub type Header = (String, String);
+
+/// Headers whose value is shown by `Debug` implementations, any other value
+/// may be a credential and is redacted.
+const DEBUG_VISIBLE_HEADERS: [&str; 10] = [
+    "accept",
+    "accept-encoding",
+    "accept-language",
+    "cache-control",
+    "content-type",
+    "if-modified-since",
+    "if-none-match",
+    "range",
+    "referer",
+    "user-agent",
+];
+
+/// `Debug` view of headers hiding values which may hold credentials.
+///
+/// ```
+/// use file_download::headers::DebugHeaders;
+/// let headers = [
+///     ("Accept".to_owned(), "*/*".to_owned()),
+///     ("Authorization".to_owned(), "Bearer abc".to_owned()),
+/// ];
+/// assert_eq!(
+///     format!("{:?}", DebugHeaders(&headers)),
+///     r#"[("Accept", "*/*"), ("Authorization", "<redacted>")]"#
+/// );
+/// ```
+pub struct DebugHeaders<'a>(pub &'a [Header]);
+
+impl Debug for DebugHeaders<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_list()
+            .entries(self.0.iter().map(|(name, value)| {
+                let visible = DEBUG_VISIBLE_HEADERS
+                    .iter()
+                    .any(|visible| name.eq_ignore_ascii_case(visible));
+                (
+                    name,
+                    if visible {
+                        value.as_str()
+                    } else {
+                        "<redacted>"
+                    },
+                )
+            }))
+            .finish()
+    }
+}
+
+/// Headers only sent to one host, see [`HeaderRules::host_headers`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct HostHeaders {
+    /// Host name compared case-insensitively, without port.
+    pub host: String,
+    pub headers: Vec<Header>,
+}
+
+impl Debug for HostHeaders {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("HostHeaders")
+            .field("host", &self.host)
+            .field("headers", &DebugHeaders(&self.headers))
+            .finish()
+    }
+}
+
+/// Headers dropped from global and per file headers when a redirect leaves
+/// the original host, host scoped credentials belong in [`HostHeaders`].
+const SENSITIVE_HEADERS: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
+
+/// Request headers of a download.
+///
+/// For every request, `user_agent` comes first then `headers`, the file own
+/// headers and at last the headers of the matching [`HostHeaders`], a later
+/// header replaces an earlier one with the same name.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HeaderRules {
+    pub user_agent: Option<String>,
+    pub headers: Vec<Header>,
+    pub host_headers: Vec<HostHeaders>,
+}
+
+impl Debug for HeaderRules {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("HeaderRules")
+            .field("user_agent", &self.user_agent)
+            .field("headers", &DebugHeaders(&self.headers))
+            .field("host_headers", &self.host_headers)
+            .finish()
+    }
+}
+
+pub(crate) fn push_header(headers: &mut Vec<Header>, (name, value): &Header) {
+    headers.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    headers.push((name.clone(), value.clone()));
+}
+
+impl HeaderRules {
+    /// Headers for a request to `url`, `cross_origin` is true once a redirect
+    /// left the origin (scheme, host and port) of the source url.
+    ///
+    /// ```
+    /// use file_download::headers::{HeaderRules, HostHeaders};
+    /// use url::Url;
+    /// let rules = HeaderRules {
+    ///     host_headers: vec![HostHeaders {
+    ///         host: "a.example.com".to_owned(),
+    ///         headers: vec![("Authorization".to_owned(), "Bearer a".to_owned())],
+    ///     }],
+    ///     ..HeaderRules::default()
+    /// };
+    /// let a = Url::parse("https://a.example.com/f").unwrap();
+    /// let b = Url::parse("https://b.example.com/f").unwrap();
+    /// assert_eq!(rules.headers_for(&a, &[], false).len(), 1);
+    /// assert!(rules.headers_for(&b, &[], true).is_empty());
+    /// ```
+    pub fn headers_for(
+        &self,
+        url: &Url,
+        file_headers: &[Header],
+        cross_origin: bool,
+    ) -> Vec<Header> {
+        let mut headers = Vec::new();
+        if let Some(user_agent) = &self.user_agent {
+            headers.push(("User-Agent".to_owned(), user_agent.clone()));
+        }
+        for header in self.headers.iter().chain(file_headers.iter()) {
+            if !cross_origin
+                || !SENSITIVE_HEADERS
+                    .iter()
+                    .any(|sensitive| header.0.eq_ignore_ascii_case(sensitive))
+            {
+                push_header(&mut headers, header);
+            }
+        }
+        if let Some(host) = url.host_str() {
+            for rule in self
+                .host_headers
+                .iter()
+                .filter(|rule| rule.host.eq_ignore_ascii_case(host))
+            {
+                for header in rule.headers.iter() {
+                    push_header(&mut headers, header);
+                }
+            }
+        }
+        headers
+    }
+}
@@ -0,0 +1,424 @@
+//! Content-addressable store of downloaded files shared between runs and
+//! projects, see [`crate::DownloadBuilder::cache`].
+//!
+//! Each entry is a file of the cache directory named after the checksum of
+//! the target it was copied from, or after the url of the file. A file found
+//! in the cache is materialised into its `.tmp` files and renamed like a
+//! downloaded one, the checked downloads are added to the cache once renamed.
+//!
+//! The validators of an entry are kept in a `.<entry>.meta` sidecar, whose
+//! modification time is the last use of the entry: the entry itself may be a
+//! hard link of a target, which must not be touched. The least recently used
+//! entries are evicted first.
+use super::{
+    check_file_size, remove_tmp_file, spawn_blocking, CheckSum, DownloadConfig, FileToDl,
+    StagedFile,
+};
+use crate::error::DlError;
+use crate::metadata::FileMetadata;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// How a target is made from a cache entry, and an entry from a target.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CacheLink {
+    /// Shares the file, a target modified in place changes the entry too,
+    /// which is then evicted on its next use when it has a checksum.
+    Hardlink,
+    /// Shares the data until one of the files is modified, on file systems
+    /// supporting it (btrfs, xfs...) on Linux.
+    #[default]
+    Reflink,
+    Copy,
+}
+
+/// A cache directory, see [`crate::DownloadBuilder::cache`].
+///
+/// ```
+/// use file_download::{Cache, CacheLink};
+///
+/// let cache = Cache {
+///     max_size: Some(10 << 30),
+///     link: CacheLink::Hardlink,
+///     ..Cache::new("/var/cache/artifacts")
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    pub dir: PathBuf,
+    /// Also caches the files without checksum, keyed by their url. Their
+    /// entry is used without asking the server whether it changed.
+    pub by_url: bool,
+    /// Largest total size of the entries in bytes, the least recently used
+    /// ones are removed past it.
+    pub max_size: Option<u64>,
+    /// Hardlinks and reflinks fall back to a copy when the file system does
+    /// not support them.
+    pub link: CacheLink,
+}
+
+impl Cache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            by_url: false,
+            max_size: None,
+            link: CacheLink::default(),
+        }
+    }
+
+    /// The entry of `file`, `None` when it cannot be cached: an extracted
+    /// archive, or a file without checksum unless keyed by url.
+    ///
+    /// The entry holds the target, a decoded file is keyed by the checksum of
+    /// the received bytes with its compression.
+    fn entry(&self, file: &FileToDl) -> Option<PathBuf> {
+        if file.extract.is_some() {
+            return None;
+        }
+        let mut key = match (&file.check_sum, self.by_url) {
+            (CheckSum::Md5(hash), _) => format!("md5-{}", hash.to_hex()),
+            (CheckSum::None, true) => format!("url-{:x}", md5::compute(&file.source)),
+            (CheckSum::None, false) => return None,
+        };
+        if let Some(compression) = file
+            .decompress
+            .filter(|_| file.check_sum == CheckSum::None || file.checks_compressed_bytes())
+        {
+            key.push_str(&format!("-{:?}", compression).to_lowercase());
+        }
+        Some(self.dir.join(key))
+    }
+
+    /// Makes `target` from `source` with the link of the cache.
+    fn link(&self, source: &Path, target: &Path) -> io::Result<()> {
+        match fs::remove_file(target) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+        let linked = match self.link {
+            CacheLink::Hardlink => fs::hard_link(source, target).is_ok(),
+            CacheLink::Reflink => reflink(source, target).is_ok(),
+            CacheLink::Copy => false,
+        };
+        if !linked {
+            let _ = fs::remove_file(target);
+            fs::copy(source, target)?;
+        }
+        Ok(())
+    }
+
+    /// Materialises `entry` into every target of `tmp_file`, returns its
+    /// validators. Fails with [`io::ErrorKind::NotFound`] without entry.
+    fn restore(&self, entry: &Path, tmp_file: &FileToDl) -> io::Result<FileMetadata> {
+        for target in tmp_file.targets() {
+            self.link(entry, Path::new(target.as_os_str()))?;
+        }
+        match fs::read_to_string(sidecar(entry)) {
+            Ok(text) => Ok(FileMetadata::parse(&text)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(FileMetadata::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Marks `entry` as used now.
+    fn touch(&self, entry: &Path) -> io::Result<()> {
+        File::options()
+            .append(true)
+            .create(true)
+            .open(sidecar(entry))?
+            .set_modified(SystemTime::now())
+    }
+
+    /// Adds `target` as `entry` with its validators, through hidden files
+    /// renamed in place.
+    fn insert(&self, entry: &Path, target: &Path, metadata: &FileMetadata) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp_entry = hidden_tmp(entry);
+        let tmp_sidecar = hidden_tmp(&sidecar(entry));
+        let result = self
+            .link(target, &tmp_entry)
+            .and_then(|()| fs::write(&tmp_sidecar, metadata.serialize()))
+            .and_then(|()| fs::rename(&tmp_entry, entry))
+            .and_then(|()| fs::rename(&tmp_sidecar, sidecar(entry)));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_entry);
+            let _ = fs::remove_file(&tmp_sidecar);
+        }
+        result
+    }
+
+    /// Removes `entry` and its sidecar.
+    fn remove(&self, entry: &Path) -> io::Result<()> {
+        for path in [entry.to_owned(), sidecar(entry)] {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the least recently used entries until they fit in
+    /// [`Cache::max_size`].
+    fn evict(&self) -> io::Result<()> {
+        let Some(max_size) = self.max_size else {
+            return Ok(());
+        };
+        let dir = match fs::read_dir(&self.dir) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            dir => dir?,
+        };
+        let mut entries = Vec::new();
+        for entry in dir {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                // an entry without sidecar was added by hand
+                let used = match fs::metadata(sidecar(&entry.path())) {
+                    Ok(sidecar) => sidecar.modified()?,
+                    Err(_) => metadata.modified()?,
+                };
+                entries.push((used, metadata.len(), entry.path()));
+            }
+        }
+        entries.sort();
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in entries {
+            if size <= max_size {
+                break;
+            }
+            self.remove(&path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+}
+
+/// The `.<entry>.meta` sidecar of `entry`.
+fn sidecar(entry: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(entry.file_name().unwrap_or_default());
+    name.push(".meta");
+    entry.with_file_name(name)
+}
+
+/// A hidden file to write `path` before renaming it, unique to the process
+/// and the call so concurrent writers of the same entry never share it.
+fn hidden_tmp(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.{}.tmp", std::process::id(), count));
+    path.with_file_name(name)
+}
+
+/// Clones the data of `source` into a new `target`.
+#[cfg(all(
+    target_os = "linux",
+    not(any(target_arch = "sparc", target_arch = "sparc64"))
+))]
+fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    let source = File::open(source)?;
+    let target = File::create_new(target)?;
+    rustix::fs::ioctl_ficlone(&target, &source).map_err(io::Error::from)
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    not(any(target_arch = "sparc", target_arch = "sparc64"))
+)))]
+fn reflink(_source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Whether the materialised `tmp_file` has the size and checksum of `file`.
+async fn restored_file_matches(file: &FileToDl, tmp_file: &FileToDl) -> bool {
+    check_file_size(file, &tmp_file.target).await.is_ok()
+        && file
+            .target_check_sum()
+            .do_file_matches_checksum(&tmp_file.target)
+            .await
+            .is_ok()
+}
+
+/// Stages the files found in the cache from their entry, returns them with
+/// the files left to download and their `.tmp` files.
+///
+/// An entry is marked as used once checked, one which does not match the
+/// checksum of its file anymore is removed and the file downloaded.
+pub(super) async fn restore_files(
+    config: &DownloadConfig,
+    files: Vec<FileToDl>,
+    tmp_files: Vec<FileToDl>,
+) -> Result<(Vec<StagedFile>, Vec<FileToDl>, Vec<FileToDl>), DlError> {
+    let Some(cache) = &config.cache else {
+        return Ok((Vec::new(), files, tmp_files));
+    };
+    let mut staged = Vec::new();
+    let (mut to_download, mut to_download_tmp) = (Vec::new(), Vec::new());
+    for (file, tmp_file) in files.into_iter().zip(tmp_files) {
+        let Some(entry) = cache.entry(&file) else {
+            to_download.push(file);
+            to_download_tmp.push(tmp_file);
+            continue;
+        };
+        let restored = {
+            let (cache, entry, tmp_file) = (cache.clone(), entry.clone(), tmp_file.clone());
+            spawn_blocking(move || cache.restore(&entry, &tmp_file)).await
+        };
+        match restored {
+            Ok(metadata) if restored_file_matches(&file, &tmp_file).await => {
+                let (cache, entry) = (cache.clone(), entry.clone());
+                spawn_blocking(move || cache.touch(&entry)).await?;
+                staged.push(StagedFile {
+                    tmp_file,
+                    file,
+                    metadata,
+                    cached: true,
+                });
+                continue;
+            }
+            Ok(_) => {
+                remove_tmp_file(&tmp_file).await?;
+                let (cache, entry) = (cache.clone(), entry.clone());
+                let _ = spawn_blocking(move || cache.remove(&entry)).await;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => {
+                remove_tmp_file(&tmp_file).await?;
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("cannot restore {}: {}", entry.display(), err),
+                )
+                .into());
+            }
+        }
+        to_download.push(file);
+        to_download_tmp.push(tmp_file);
+    }
+    Ok((staged, to_download, to_download_tmp))
+}
+
+/// Adds the renamed targets of the downloaded `staged` files to the cache,
+/// then evicts the least recently used entries.
+pub(super) async fn populate<'a>(
+    config: &DownloadConfig,
+    staged: impl Iterator<Item = &'a StagedFile>,
+) -> Result<(), DlError> {
+    let Some(cache) = &config.cache else {
+        return Ok(());
+    };
+    let entries: Vec<_> = staged
+        .filter(|staged| !staged.cached)
+        .filter_map(|staged| {
+            let entry = cache.entry(&staged.file)?;
+            let target = PathBuf::from(staged.file.target.as_os_str());
+            Some((entry, target, staged.metadata.clone()))
+        })
+        .collect();
+    let cache = cache.clone();
+    spawn_blocking(move || {
+        for (entry, target, metadata) in entries {
+            cache.insert(&entry, &target, &metadata)?;
+        }
+        cache.evict()
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{file, temp_target};
+    use std::time::Duration;
+
+    #[test]
+    fn test_least_recently_used_entries_are_evicted() {
+        let (dir, target) = temp_target("a.txt");
+        let cache = Cache {
+            max_size: Some(10),
+            ..Cache::new(dir.path().join("cache"))
+        };
+        std::fs::write(&target, "hello").unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("old", 20), ("recent", 10), ("new", 0)] {
+            let entry = cache.dir.join(name);
+            cache
+                .insert(&entry, &target, &FileMetadata::default())
+                .unwrap();
+            File::options()
+                .write(true)
+                .open(sidecar(&entry))
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        cache.evict().unwrap();
+
+        let mut names: Vec<_> = std::fs::read_dir(&cache.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, [".new.meta", ".recent.meta", "new", "recent"]);
+    }
+
+    #[test]
+    fn test_hard_linked_targets_are_not_touched() {
+        let (dir, target) = temp_target("a.txt");
+        let cache = Cache {
+            link: CacheLink::Hardlink,
+            ..Cache::new(dir.path().join("cache"))
+        };
+        std::fs::write(&target, "hello").unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&target)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let metadata = FileMetadata {
+            etag: Some("\"v1\"".to_owned()),
+            last_modified: None,
+        };
+        let entry = cache.dir.join("a");
+
+        cache.insert(&entry, &target, &metadata).unwrap();
+        cache.touch(&entry).unwrap();
+
+        let tmp_file = file(&dir, "b.txt", CheckSum::None);
+        assert_eq!(cache.restore(&entry, &tmp_file).unwrap(), metadata);
+        assert_eq!(
+            std::fs::metadata(&target).unwrap().modified().unwrap(),
+            modified
+        );
+    }
+
+    #[test]
+    fn test_links_fall_back_to_copies() {
+        let (dir, source) = temp_target("a.txt");
+        std::fs::write(&source, "hello").unwrap();
+        for link in [CacheLink::Hardlink, CacheLink::Reflink, CacheLink::Copy] {
+            let cache = Cache {
+                link,
+                ..Cache::new(dir.path())
+            };
+            let target = dir.path().join(format!("{:?}", link));
+            std::fs::write(&target, "old").unwrap();
+            cache.link(&source, &target).unwrap();
+            assert_eq!(std::fs::read(&target).unwrap(), b"hello");
+        }
+    }
+}
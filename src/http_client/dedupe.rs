@@ -0,0 +1,177 @@
+//! Files given several times to a [`crate::DownloadBuilder`], a source is
+//! fetched once for all of its targets and a target written once.
+use super::{handler_factory, DownloadConfig, FileToDl};
+use crate::error::{ConflictingTargetsError, DlError};
+use std::collections::HashMap;
+
+#[cfg(feature = "async-std")]
+use async_std::path::PathBuf;
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use std::path::PathBuf;
+
+/// Whether `a` and `b` get the same file from the same request, so one
+/// download can be written to the targets of both.
+fn same_file(config: &DownloadConfig, a: &FileToDl, b: &FileToDl) -> bool {
+    a.source == b.source
+        && a.check_sum == b.check_sum
+        && a.check_sum_of == b.check_sum_of
+        && a.headers == b.headers
+        && a.existing_file_policy == b.existing_file_policy
+        && a.expected_size == b.expected_size
+        && a.max_file_size == b.max_file_size
+        && a.decompress == b.decompress
+        && a.extract == b.extract
+        && handler_factory(config, a).is_none()
+        && handler_factory(config, b).is_none()
+}
+
+/// `files` without duplicates: a target given again with the same source and
+/// settings is dropped, and the files with the same source and settings are
+/// merged into one whose [`FileToDl::extra_targets`] are the other targets.
+///
+/// Fails with a [`ConflictingTargetsError`] when a target is given again with
+/// another source or other settings, or with a handler.
+pub(crate) fn dedupe_files<'a>(
+    config: &DownloadConfig,
+    files: impl Iterator<Item = &'a FileToDl>,
+) -> Result<Vec<FileToDl>, DlError> {
+    let mut deduped: Vec<FileToDl> = Vec::new();
+    // index in `deduped` of the file writing each target
+    let mut writers: HashMap<PathBuf, usize> = HashMap::new();
+    let mut by_source: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut conflicts = Vec::new();
+    for file in files {
+        let mut targets: Vec<PathBuf> = Vec::new();
+        for target in file.targets() {
+            match writers.get(target).map(|i| &deduped[*i]) {
+                Some(other) if same_file(config, other, file) => {}
+                Some(_) => conflicts.push(std::path::PathBuf::from(target.as_os_str())),
+                None if targets.contains(target) => {}
+                None => targets.push(target.clone()),
+            }
+        }
+        if targets.is_empty() {
+            continue;
+        }
+        let sources = by_source.entry(&file.source).or_default();
+        let i = match sources
+            .iter()
+            .find(|i| same_file(config, &deduped[**i], file))
+        {
+            Some(&i) => {
+                deduped[i].extra_targets.extend(targets.iter().cloned());
+                i
+            }
+            None => {
+                sources.push(deduped.len());
+                deduped.push(FileToDl {
+                    target: targets[0].clone(),
+                    extra_targets: targets[1..].to_vec(),
+                    ..file.clone()
+                });
+                deduped.len() - 1
+            }
+        };
+        for target in targets {
+            writers.insert(target, i);
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(ConflictingTargetsError::from(conflicts).into());
+    }
+    Ok(deduped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{file, md5, HELLO_MD5};
+    use crate::CheckSum;
+
+    #[test]
+    fn test_sources_are_fetched_once() {
+        let config = DownloadConfig::default();
+        let files = [
+            file("a", "x.txt", CheckSum::None),
+            file("a", "y.txt", CheckSum::None),
+            file("b", "x.txt", CheckSum::None),
+            file("a", "x.txt", CheckSum::None),
+            FileToDl {
+                decompress: Some(crate::Compression::Gzip),
+                ..file("c", "x.txt", CheckSum::None)
+            },
+        ];
+
+        let deduped = dedupe_files(&config, files.iter()).unwrap();
+
+        assert_eq!(deduped.len(), 3);
+        assert_eq!(deduped[0].target, PathBuf::from("a/x.txt"));
+        assert_eq!(deduped[0].extra_targets, [PathBuf::from("b/x.txt")]);
+        assert_eq!(deduped[1].target, PathBuf::from("a/y.txt"));
+        assert_eq!(deduped[2].target, PathBuf::from("c/x.txt"));
+        assert!(deduped[2].extra_targets.is_empty());
+    }
+
+    struct NotCalled;
+
+    impl crate::handler::HandlerFactory for NotCalled {
+        fn create(&self, _: &FileToDl) -> std::io::Result<Box<dyn crate::handler::BodyHandler>> {
+            unreachable!("nothing is downloaded")
+        }
+    }
+
+    #[test]
+    fn test_targets_with_other_settings_are_rejected() {
+        let config = DownloadConfig::default();
+        let conflicting = [
+            FileToDl {
+                decompress: Some(crate::Compression::Gzip),
+                ..file("a", "x.txt", CheckSum::None)
+            },
+            FileToDl {
+                extract: Some(crate::Extract::new(crate::ArchiveFormat::Tar)),
+                ..file("a", "x.txt", CheckSum::None)
+            },
+            FileToDl {
+                handler: Some(std::sync::Arc::new(NotCalled)),
+                ..file("a", "x.txt", CheckSum::None)
+            },
+        ];
+        for other in conflicting {
+            let files = [file("a", "x.txt", CheckSum::None), other];
+            match dedupe_files(&config, files.iter()) {
+                Err(DlError::ConflictingTargetsError(_)) => {}
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_conflicting_targets_are_rejected() {
+        let config = DownloadConfig::default();
+        let checked = FileToDl {
+            check_sum: md5(HELLO_MD5),
+            ..file("a", "y.txt", CheckSum::None)
+        };
+        let files = [
+            file("a", "x.txt", CheckSum::None),
+            file("a", "y.txt", CheckSum::None),
+            FileToDl {
+                source: "https://example.com/z.txt".to_owned(),
+                ..file("a", "x.txt", CheckSum::None)
+            },
+            checked,
+        ];
+
+        match dedupe_files(&config, files.iter()) {
+            Err(DlError::ConflictingTargetsError(err)) => assert_eq!(
+                err.targets,
+                [
+                    std::path::PathBuf::from("a/x.txt"),
+                    std::path::PathBuf::from("a/y.txt")
+                ]
+            ),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+}
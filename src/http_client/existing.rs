@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use std::time::SystemTime;
+
+#[cfg(feature = "async-std")]
+use async_std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use std::path::{Path, PathBuf};
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use tokio::{fs, io};
+
+/// What to do when the target of a file already exists.
+///
+/// Set for a whole download with [`crate::DownloadBuilder::existing_file_policy`],
+/// for a folder with [`crate::DownloadFolder::existing_file_policy`] or for a
+/// single file with [`crate::FileToDl::existing_file_policy`], the most
+/// specific one is used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ExistingFilePolicy {
+    /// Downloads the file again and replaces it.
+    #[default]
+    Overwrite,
+    /// Keeps the existing file without any request. With a
+    /// [`crate::CheckSum`] it is verified first and downloaded again on
+    /// mismatch, so an interrupted or corrupted copy is replaced.
+    Skip,
+    /// Like `Skip`, but a file without [`crate::CheckSum`] is always
+    /// downloaded again.
+    SkipIfChecksumMatches,
+    /// Sends the modification time of the existing file as
+    /// `If-Modified-Since` and keeps it unless the server has a newer one
+    /// according to `Last-Modified`.
+    SkipIfNewerOrSame,
+    /// Renames the existing file to `<name>.bak`, or `<name>.<timestamp>.bak`
+    /// when taken, before replacing it.
+    Backup,
+    /// Fails the download before any transfer.
+    Error,
+}
+
+/// `time` formatted for `If-Modified-Since`.
+pub(crate) fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(SystemTime::from)
+}
+
+pub(crate) async fn modified(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path).await?.modified()
+}
+
+fn with_suffix(target: &Path, suffix: &str) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_owned();
+    name.push(suffix);
+    target.with_file_name(name)
+}
+
+/// First free backup name of `target`.
+pub(crate) async fn backup_path(target: &Path) -> PathBuf {
+    let backup = with_suffix(target, ".bak");
+    if fs::metadata(&backup).await.is_err() {
+        return backup;
+    }
+    let timestamp = Utc::now().format("%Y%m%d%H%M%S");
+    let mut backup = with_suffix(target, &format!(".{}.bak", timestamp));
+    let mut index = 1;
+    while fs::metadata(&backup).await.is_ok() {
+        backup = with_suffix(target, &format!(".{}-{}.bak", timestamp, index));
+        index += 1;
+    }
+    backup
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_http_date_round_trip() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        assert_eq!(http_date(time), "Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(parse_http_date(&http_date(time)), Some(time));
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,692 @@
+//! Unpacking of downloaded archives, see [`FileToDl::extract`].
+//!
+//! The checked `.tmp` archive is extracted into a fresh `.extract` sibling
+//! directory which then takes its place, so the target is renamed like any
+//! other staged file.
+#[cfg(feature = "extract")]
+use super::spawn_blocking;
+use super::FileToDl;
+use crate::auth::redact_url;
+use crate::error::*;
+
+#[cfg(feature = "extract")]
+use std::fs::{self, File};
+use std::io;
+#[cfg(feature = "extract")]
+use std::io::Read;
+#[cfg(feature = "extract")]
+use std::path::{Component, Path, PathBuf};
+
+/// Format of an archive unpacked with an [`Extract`], a compressed tar is
+/// decoded with [`FileToDl::decompress`] first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+/// Unpacks a downloaded archive into its target directory, see
+/// [`FileToDl::extract`].
+///
+/// Entries with a `..` component or an absolute path, symbolic links
+/// pointing outside of the directory and entries written through a symbolic
+/// link are rejected. Symbolic links are only created on unix, devices and
+/// fifos are skipped.
+///
+/// Extraction needs the `extract` feature, downloads of archives to extract
+/// fail with an I/O error without it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extract {
+    pub format: ArchiveFormat,
+    /// Leading components removed from the entry paths, like
+    /// `tar --strip-components`, entries without any left are skipped.
+    pub strip_components: usize,
+    /// Largest total size of the extracted files.
+    pub max_size: Option<u64>,
+    /// Largest number of entries, of any kind.
+    pub max_entries: Option<u64>,
+    /// Applies the unix permissions of the entries, without the setuid,
+    /// setgid and sticky bits.
+    pub preserve_permissions: bool,
+}
+
+impl Extract {
+    /// Extraction of a `format` archive without limits, keeping the permissions.
+    pub fn new(format: ArchiveFormat) -> Self {
+        Self {
+            format,
+            strip_components: 0,
+            max_size: None,
+            max_entries: None,
+            preserve_permissions: true,
+        }
+    }
+}
+
+/// Replaces the staged archives of `file`, one per target, with the
+/// directory they hold, an archive is kept when it cannot be extracted.
+pub(super) async fn extract_staged(
+    tmp_file: &FileToDl,
+    file: &FileToDl,
+    durable: bool,
+) -> Result<(), DlError> {
+    let Some(extract) = &file.extract else {
+        return Ok(());
+    };
+    #[cfg(feature = "extract")]
+    for archive in tmp_file.targets() {
+        let archive = PathBuf::from(archive.as_os_str());
+        let (extract, url) = (extract.clone(), redact_url(&file.source));
+        spawn_blocking(move || Ok(unpack(&archive, &extract, &url, durable))).await??;
+    }
+    #[cfg(feature = "extract")]
+    return Ok(());
+    #[cfg(not(feature = "extract"))]
+    {
+        let _ = (tmp_file, durable);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "extracting {} as {:?} needs the `extract` feature",
+                redact_url(&file.source),
+                extract.format
+            ),
+        )
+        .into())
+    }
+}
+
+#[cfg(feature = "extract")]
+fn unpack(archive: &Path, extract: &Extract, url: &str, durable: bool) -> Result<(), DlError> {
+    let mut name = archive.file_name().unwrap_or_default().to_owned();
+    name.push(".extract");
+    let root = archive.with_file_name(name);
+    // left by an interrupted extraction
+    match fs::remove_dir_all(&root) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    fs::create_dir(&root)?;
+    let mut unpacker = Unpacker {
+        root: &root,
+        extract,
+        url,
+        durable,
+        entries: 0,
+        size: 0,
+        created_dirs: Vec::new(),
+        dir_modes: Vec::new(),
+    };
+    let result = File::open(archive)
+        .map_err(DlError::from)
+        .and_then(|archive| match extract.format {
+            ArchiveFormat::Tar => unpacker.unpack_tar(archive),
+            ArchiveFormat::Zip => unpacker.unpack_zip(archive),
+        })
+        .and_then(|()| unpacker.finish());
+    if let Err(err) = result {
+        let _ = fs::remove_dir_all(&root);
+        return Err(err);
+    }
+    fs::remove_file(archive)?;
+    fs::rename(&root, archive)?;
+    Ok(())
+}
+
+/// Writes the entries of an archive below `root`, counting them against the
+/// limits.
+#[cfg(feature = "extract")]
+struct Unpacker<'a> {
+    root: &'a Path,
+    extract: &'a Extract,
+    url: &'a str,
+    durable: bool,
+    entries: u64,
+    size: u64,
+    created_dirs: Vec<PathBuf>,
+    dir_modes: Vec<(PathBuf, u32)>,
+}
+
+#[cfg(feature = "extract")]
+impl Unpacker<'_> {
+    fn error(&self, reason: ExtractErrorReason) -> DlError {
+        ExtractError {
+            url: self.url.to_owned(),
+            reason,
+        }
+        .into()
+    }
+
+    fn reject(&self, entry: &Path, reason: UnsafePathReason) -> DlError {
+        self.error(ExtractErrorReason::UnsafeEntry(entry.to_owned(), reason))
+    }
+
+    fn count_entry(&mut self) -> Result<(), DlError> {
+        self.entries += 1;
+        match self.extract.max_entries {
+            Some(max_entries) if self.entries > max_entries => {
+                Err(self.error(ExtractErrorReason::TooManyEntries(max_entries)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Where `entry` is extracted, `None` when no component is left once
+    /// the leading ones are stripped.
+    fn entry_path(&self, entry: &Path) -> Result<Option<PathBuf>, DlError> {
+        let mut names = Vec::new();
+        for component in entry.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                Component::CurDir => {}
+                Component::ParentDir => return Err(self.reject(entry, UnsafePathReason::ParentDir)),
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(self.reject(entry, UnsafePathReason::Absolute))
+                }
+            }
+        }
+        Ok(names
+            .get(self.extract.strip_components..)
+            .filter(|names| !names.is_empty())
+            .map(|names| self.root.join(names.iter().collect::<PathBuf>())))
+    }
+
+    /// Walks the directories from the root to `dir`, creating the missing
+    /// ones with `create`, none of them may be a symbolic link.
+    fn walk_dirs(&mut self, entry: &Path, dir: &Path, create: bool) -> Result<(), DlError> {
+        let relative = dir.strip_prefix(self.root).unwrap_or(dir);
+        let mut path = self.root.to_owned();
+        for name in relative.components() {
+            path.push(name);
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    return Err(self.reject(entry, UnsafePathReason::SymlinkedParent(path)))
+                }
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound && create => {
+                    fs::create_dir(&path)?;
+                    self.created_dirs.push(path.clone());
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the directories of `path` and removes what an earlier entry
+    /// left there, so nothing is written through a symbolic link.
+    fn prepare(&mut self, entry: &Path, path: &Path) -> Result<(), DlError> {
+        self.walk_dirs(entry, path.parent().unwrap_or(self.root), true)?;
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.is_dir() => fs::remove_file(path)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn dir(&mut self, entry: &Path, path: &Path, mode: Option<u32>) -> Result<(), DlError> {
+        self.walk_dirs(entry, path, true)?;
+        if let Some(mode) = mode.filter(|_| self.extract.preserve_permissions) {
+            self.dir_modes.push((path.to_owned(), mode));
+        }
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        entry: &Path,
+        path: &Path,
+        reader: &mut dyn Read,
+        mode: Option<u32>,
+    ) -> Result<(), DlError> {
+        self.prepare(entry, path)?;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        // the declared sizes are not trusted, one byte past the limit is enough
+        let written = match self.extract.max_size {
+            Some(max_size) => {
+                let remaining = max_size.saturating_sub(self.size).saturating_add(1);
+                io::copy(&mut reader.take(remaining), &mut file)?
+            }
+            None => io::copy(reader, &mut file)?,
+        };
+        self.size += written;
+        if let Some(max_size) = self.extract.max_size {
+            if self.size > max_size {
+                return Err(self.error(ExtractErrorReason::TooLarge(max_size)));
+            }
+        }
+        if let Some(mode) = mode.filter(|_| self.extract.preserve_permissions) {
+            set_mode(path, mode & 0o777)?;
+        }
+        if self.durable {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Creates a symbolic link to `link`, which must stay inside the root.
+    ///
+    /// `link` is resolved against what is already extracted: none of the
+    /// directories it goes through may be a symbolic link, and a directory it
+    /// leaves with `..` must already exist, so that no later entry can turn
+    /// it into one.
+    fn symlink(&mut self, entry: &Path, path: &Path, link: &Path) -> Result<(), DlError> {
+        self.prepare(entry, path)?;
+        let mut resolved = path.parent().unwrap_or(self.root).to_owned();
+        let mut components = link.components().peekable();
+        while let Some(component) = components.next() {
+            match component {
+                Component::Normal(name) => {
+                    resolved.push(name);
+                    if components.peek().is_none() {
+                        continue;
+                    }
+                    match fs::symlink_metadata(&resolved) {
+                        Ok(metadata) if metadata.file_type().is_symlink() => {
+                            return Err(
+                                self.reject(entry, UnsafePathReason::SymlinkedParent(resolved))
+                            )
+                        }
+                        Ok(_) => {}
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    let is_dir = fs::symlink_metadata(&resolved).is_ok_and(|m| m.is_dir());
+                    if resolved == self.root || !is_dir {
+                        return Err(self.reject(entry, UnsafePathReason::ParentDir));
+                    }
+                    resolved.pop();
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(self.reject(entry, UnsafePathReason::Absolute))
+                }
+            }
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(link, path)?;
+        Ok(())
+    }
+
+    /// Links `path` to the file extracted from the entry `link`.
+    fn hard_link(&mut self, entry: &Path, path: &Path, link: &Path) -> Result<(), DlError> {
+        let Some(source) = self.entry_path(link)? else {
+            return Err(self.reject(entry, UnsafePathReason::Empty));
+        };
+        self.walk_dirs(entry, source.parent().unwrap_or(self.root), false)?;
+        if fs::symlink_metadata(&source)?.file_type().is_symlink() {
+            return Err(self.reject(entry, UnsafePathReason::SymlinkedParent(source)));
+        }
+        self.prepare(entry, path)?;
+        fs::hard_link(source, path)?;
+        Ok(())
+    }
+
+    fn unpack_tar(&mut self, archive: File) -> Result<(), DlError> {
+        let mut archive = tar::Archive::new(archive);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            self.count_entry()?;
+            let name = entry.path()?.into_owned();
+            let Some(path) = self.entry_path(&name)? else {
+                continue;
+            };
+            let mode = entry.header().mode().ok();
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => self.dir(&name, &path, mode)?,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    self.file(&name, &path, &mut entry, mode)?
+                }
+                tar::EntryType::Symlink => {
+                    let link = entry.link_name()?.unwrap_or_default().into_owned();
+                    self.symlink(&name, &path, &link)?
+                }
+                tar::EntryType::Link => {
+                    let link = entry.link_name()?.unwrap_or_default().into_owned();
+                    self.hard_link(&name, &path, &link)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn unpack_zip(&mut self, archive: File) -> Result<(), DlError> {
+        let mut archive = zip::ZipArchive::new(archive).map_err(zip_error)?;
+        if let Some(max_entries) = self.extract.max_entries {
+            // known from the central directory, before anything is written
+            if archive.len() as u64 > max_entries {
+                return Err(self.error(ExtractErrorReason::TooManyEntries(max_entries)));
+            }
+        }
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(zip_error)?;
+            self.count_entry()?;
+            let name = PathBuf::from(entry.name());
+            let Some(path) = self.entry_path(&name)? else {
+                continue;
+            };
+            let mode = entry.unix_mode();
+            if entry.is_dir() {
+                self.dir(&name, &path, mode)?;
+            } else if entry.is_symlink() {
+                let mut link = String::new();
+                (&mut entry).take(4096).read_to_string(&mut link)?;
+                self.symlink(&name, &path, Path::new(&link))?;
+            } else {
+                self.file(&name, &path, &mut entry, mode)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the directory permissions once their content is written, the
+    /// owner keeps full access so the target can still be replaced.
+    fn finish(&mut self) -> Result<(), DlError> {
+        for (path, mode) in self.dir_modes.iter().rev() {
+            set_mode(path, mode & 0o777 | 0o700)?;
+        }
+        if self.durable {
+            for dir in self.created_dirs.iter().rev() {
+                sync_dir(dir)?;
+            }
+            sync_dir(self.root)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "extract")]
+fn zip_error(err: zip::result::ZipError) -> io::Error {
+    match err {
+        zip::result::ZipError::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+#[cfg(all(feature = "extract", unix))]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(all(feature = "extract", not(unix)))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(feature = "extract", unix))]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(all(feature = "extract", not(unix)))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, feature = "extract"))]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn file_header(len: usize, mode: u32) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(len as u64);
+        header.set_mode(mode);
+        header.set_cksum();
+        header
+    }
+
+    fn symlink_header(link: &str) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_link_name_literal(link).unwrap();
+        header.set_size(0);
+        header.set_cksum();
+        header
+    }
+
+    fn tar_archive(build: impl FnOnce(&mut tar::Builder<Vec<u8>>)) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        build(&mut builder);
+        builder.into_inner().unwrap()
+    }
+
+    fn zip_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    type DlResult = Result<(), DlError>;
+
+    /// Extracts `data` from a `.tmp` archive in a temporary directory.
+    fn unpack_data(data: &[u8], extract: &Extract) -> (tempfile::TempDir, PathBuf, DlResult) {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("a.tar.tmp");
+        fs::write(&archive, data).unwrap();
+        let result = unpack(&archive, extract, "https://example.com/a.tar", false);
+        (dir, archive, result)
+    }
+
+    fn reason(result: DlResult) -> ExtractErrorReason {
+        match result {
+            Err(DlError::ExtractError(err)) => err.reason,
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_tar_entries_are_extracted() {
+        let data = tar_archive(|builder| {
+            builder
+                .append_data(
+                    &mut file_header(5, 0o755),
+                    "pkg-1.0/bin/run.sh",
+                    &b"hello"[..],
+                )
+                .unwrap();
+            builder
+                .append_data(&mut file_header(3, 0o4644), "pkg-1.0/README", &b"abc"[..])
+                .unwrap();
+            builder
+                .append_data(
+                    &mut symlink_header("bin/run.sh"),
+                    "pkg-1.0/run",
+                    io::empty(),
+                )
+                .unwrap();
+        });
+        let extract = Extract {
+            strip_components: 1,
+            ..Extract::new(ArchiveFormat::Tar)
+        };
+        let (dir, target, result) = unpack_data(&data, &extract);
+        result.unwrap();
+
+        assert_eq!(fs::read(target.join("bin/run.sh")).unwrap(), b"hello");
+        assert_eq!(fs::read(target.join("README")).unwrap(), b"abc");
+        assert!(!dir.path().join("a.tar.tmp.extract").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &str| {
+                let metadata = fs::metadata(target.join(path)).unwrap();
+                metadata.permissions().mode() & 0o7777
+            };
+            assert_eq!(mode("bin/run.sh"), 0o755);
+            // without the setuid bit
+            assert_eq!(mode("README"), 0o644);
+            assert_eq!(fs::read(target.join("run")).unwrap(), b"hello");
+        }
+    }
+
+    #[test]
+    fn test_zip_entries_are_extracted() {
+        let data = zip_archive(&[("a.txt", b"hello"), ("sub/b.txt", b"world")]);
+        let (_dir, target, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Zip));
+        result.unwrap();
+
+        assert_eq!(fs::read(target.join("a.txt")).unwrap(), b"hello");
+        assert_eq!(fs::read(target.join("sub/b.txt")).unwrap(), b"world");
+    }
+
+    #[test]
+    fn test_unsafe_entries_are_rejected() {
+        let data = zip_archive(&[("a.txt", b"hello"), ("../evil.txt", b"evil")]);
+        let (dir, target, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Zip));
+        assert_eq!(
+            reason(result),
+            ExtractErrorReason::UnsafeEntry("../evil.txt".into(), UnsafePathReason::ParentDir)
+        );
+        assert!(!dir.path().join("evil.txt").exists());
+        assert!(!dir.path().join("a.tar.tmp.extract").exists());
+        // the archive is kept
+        assert!(target.is_file());
+
+        let data = tar_archive(|builder| {
+            builder
+                .append_data(&mut symlink_header("../.."), "link", io::empty())
+                .unwrap();
+        });
+        let (_dir, _, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Tar));
+        assert_eq!(
+            reason(result),
+            ExtractErrorReason::UnsafeEntry("link".into(), UnsafePathReason::ParentDir)
+        );
+
+        let data = tar_archive(|builder| {
+            builder
+                .append_data(&mut symlink_header("/etc"), "link", io::empty())
+                .unwrap();
+        });
+        let (_dir, _, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Tar));
+        assert_eq!(
+            reason(result),
+            ExtractErrorReason::UnsafeEntry("link".into(), UnsafePathReason::Absolute)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_entries_are_not_written_through_symlinks() {
+        let data = tar_archive(|builder| {
+            builder
+                .append_data(&mut symlink_header("."), "sub", io::empty())
+                .unwrap();
+            builder
+                .append_data(&mut file_header(5, 0o644), "sub/a.txt", &b"hello"[..])
+                .unwrap();
+        });
+        let (dir, _, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Tar));
+        assert_eq!(
+            reason(result),
+            ExtractErrorReason::UnsafeEntry(
+                "sub/a.txt".into(),
+                UnsafePathReason::SymlinkedParent(dir.path().join("a.tar.tmp.extract/sub"))
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_chained_symlinks_are_rejected() {
+        let data = tar_archive(|builder| {
+            builder
+                .append_data(&mut symlink_header("."), "l1", io::empty())
+                .unwrap();
+            builder
+                .append_data(&mut symlink_header("l1/l1/../.."), "l2", io::empty())
+                .unwrap();
+        });
+        let (dir, _, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Tar));
+        assert_eq!(
+            reason(result),
+            ExtractErrorReason::UnsafeEntry(
+                "l2".into(),
+                UnsafePathReason::SymlinkedParent(dir.path().join("a.tar.tmp.extract/l1"))
+            )
+        );
+        assert!(!dir.path().join("a.tar.tmp.extract").exists());
+
+        // a directory left with `..` must exist before the link
+        let data = tar_archive(|builder| {
+            builder
+                .append_data(&mut symlink_header("a/b/../.."), "l2", io::empty())
+                .unwrap();
+            builder
+                .append_data(&mut symlink_header("."), "a", io::empty())
+                .unwrap();
+        });
+        let (_dir, _, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Tar));
+        assert_eq!(
+            reason(result),
+            ExtractErrorReason::UnsafeEntry("l2".into(), UnsafePathReason::ParentDir)
+        );
+
+        let data = tar_archive(|builder| {
+            builder
+                .append_data(&mut file_header(5, 0o644), "sub/a.txt", &b"hello"[..])
+                .unwrap();
+            builder
+                .append_data(&mut symlink_header("../sub/a.txt"), "sub/link", io::empty())
+                .unwrap();
+        });
+        let (_dir, target, result) = unpack_data(&data, &Extract::new(ArchiveFormat::Tar));
+        result.unwrap();
+        assert_eq!(fs::read(target.join("sub/link")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_limits_stop_archive_bombs() {
+        let data = zip_archive(&[("a.txt", b"hello"), ("b.txt", b"world")]);
+        let extract = Extract {
+            max_entries: Some(1),
+            ..Extract::new(ArchiveFormat::Zip)
+        };
+        let (_dir, _, result) = unpack_data(&data, &extract);
+        assert_eq!(reason(result), ExtractErrorReason::TooManyEntries(1));
+
+        let extract = Extract {
+            max_size: Some(8),
+            ..Extract::new(ArchiveFormat::Zip)
+        };
+        let (dir, _, result) = unpack_data(&data, &extract);
+        assert_eq!(reason(result), ExtractErrorReason::TooLarge(8));
+        assert!(!dir.path().join("a.tar.tmp.extract").exists());
+
+        let data = tar_archive(|builder| {
+            for name in ["a.txt", "b.txt", "c.txt"] {
+                builder
+                    .append_data(&mut file_header(5, 0o644), name, &b"hello"[..])
+                    .unwrap();
+            }
+        });
+        let extract = Extract {
+            max_entries: Some(2),
+            ..Extract::new(ArchiveFormat::Tar)
+        };
+        let (_dir, _, result) = unpack_data(&data, &extract);
+        assert_eq!(reason(result), ExtractErrorReason::TooManyEntries(2));
+
+        let extract = Extract {
+            max_size: Some(15),
+            ..Extract::new(ArchiveFormat::Tar)
+        };
+        let (_dir, target, result) = unpack_data(&data, &extract);
+        result.unwrap();
+        assert_eq!(fs::read(target.join("c.txt")).unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,98 @@
+//! Free space pre-flight, see [`crate::DownloadBuilder::check_free_space`].
+use super::{fetch_files, DownloadConfig, FileToDl};
+use crate::error::{DlError, InsufficientSpaceError};
+use crate::transport::{HttpVersion, Method};
+
+/// `Content-Length` of each file from a `HEAD` request, `0` when unknown or
+/// when the file would not be written.
+async fn announced_sizes(
+    config: &DownloadConfig,
+    tmp_files: &[FileToDl],
+    version: HttpVersion,
+) -> Result<Vec<u64>, DlError> {
+    Ok(fetch_files(config, tmp_files, Method::Head, version, false)
+        .await?
+        .iter()
+        .map(|response| {
+            response
+                .header("Content-Length")
+                .filter(|_| response.is_success())
+                .and_then(|len| len.parse().ok())
+                .unwrap_or(0)
+        })
+        .collect())
+}
+
+/// Fails when the announced sizes of `tmp_files` exceed the space available
+/// on their file systems, only checked on unix.
+///
+/// Files that are decompressed or extracted are not counted, their
+/// `Content-Length` says nothing about the size they take on disk.
+pub(super) async fn check_free_space(
+    config: &DownloadConfig,
+    tmp_files: &[FileToDl],
+    version: HttpVersion,
+) -> Result<(), DlError> {
+    let counted: Vec<FileToDl> = tmp_files
+        .iter()
+        .filter(|file| file.decompress.is_none() && file.extract.is_none())
+        .cloned()
+        .collect();
+    if counted.is_empty() {
+        return Ok(());
+    }
+    let sizes = announced_sizes(config, &counted, version).await?;
+    #[cfg(unix)]
+    {
+        use std::path::PathBuf;
+
+        // (directory of a target, its announced size)
+        let dirs: Vec<(PathBuf, u64)> = counted
+            .iter()
+            .zip(sizes)
+            .flat_map(|(file, size)| {
+                file.targets().map(move |target| {
+                    let target = std::path::Path::new(target.as_os_str());
+                    let dir = match target.parent() {
+                        Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+                        _ => PathBuf::from("."),
+                    };
+                    (dir, size)
+                })
+            })
+            .collect();
+        super::spawn_blocking(move || Ok(check_devices(dirs))).await??;
+    }
+    #[cfg(not(unix))]
+    let _ = sizes;
+    Ok(())
+}
+
+/// Sums the sizes of `dirs` per device and compares them to its free space.
+#[cfg(unix)]
+fn check_devices(dirs: Vec<(std::path::PathBuf, u64)>) -> Result<(), DlError> {
+    use std::os::unix::fs::MetadataExt;
+
+    // (device, a directory on it, required bytes)
+    let mut required: Vec<(u64, std::path::PathBuf, u64)> = Vec::new();
+    for (dir, size) in dirs {
+        let device = std::fs::metadata(&dir)?.dev();
+        match required.iter_mut().find(|(other, _, _)| *other == device) {
+            Some((_, _, total)) => *total += size,
+            None => required.push((device, dir, size)),
+        }
+    }
+    for (_, dir, required) in required {
+        let stat = rustix::fs::statvfs(&dir).map_err(std::io::Error::from)?;
+        let available = stat.f_bavail.saturating_mul(stat.f_frsize);
+        if required > available {
+            return Err(InsufficientSpaceError {
+                dir,
+                required,
+                available,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use super::{compare_md5, CheckSum, DownloadConfig, FileToDl, Hops};
+use crate::auth::redact_url;
+use crate::error::*;
+#[cfg(feature = "decompression")]
+use crate::handler::Decompressor;
+use crate::transport::{BodyStream, HttpVersion, Method};
+use bytes::Bytes;
+use futures::ready;
+use futures::stream::{self, Stream, StreamExt};
+use std::io;
+#[cfg(feature = "decompression")]
+use std::io::Write;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The body of a [`FileToDl`] decoded and checked while it is read.
+///
+/// The limits fail the stream as soon as they are crossed, the size and
+/// checksum are compared once the body ends: the last item is then an error
+/// instead of the end of the stream.
+struct CheckedBody {
+    body: BodyStream,
+    url: String,
+    check_sum: CheckSum,
+    expected_size: Option<u64>,
+    max_file_size: Option<u64>,
+    #[cfg(feature = "decompression")]
+    decoder: Option<Box<Decompressor<Vec<u8>>>>,
+    received_md5: Option<md5::Context>,
+    decoded_md5: Option<md5::Context>,
+    size: u64,
+    done: bool,
+}
+
+/// `err` as the error of a download.
+fn check_hash_error(err: CheckHashError) -> DlError {
+    match err {
+        CheckHashError::IoError(err) => err.into(),
+        CheckHashError::HashError(err) => BadCheckSumError {
+            file_sources: vec![err],
+            file_sizes: Vec::new(),
+        }
+        .into(),
+        CheckHashError::SizeError(err) => BadCheckSumError {
+            file_sources: Vec::new(),
+            file_sizes: vec![err],
+        }
+        .into(),
+    }
+}
+
+impl CheckedBody {
+    fn new(file: &FileToDl, body: BodyStream, max_file_size: Option<u64>) -> io::Result<Self> {
+        let md5 = || match file.check_sum {
+            CheckSum::Md5(_) => Some(md5::Context::new()),
+            CheckSum::None => None,
+        };
+        let (received_md5, decoded_md5) = match file.checks_compressed_bytes() {
+            true => (md5(), None),
+            false => (None, md5()),
+        };
+        #[cfg(feature = "decompression")]
+        let decoder = match file.decompress {
+            Some(compression) => Some(Box::new(Decompressor::new(compression, Vec::new())?)),
+            None => None,
+        };
+        #[cfg(not(feature = "decompression"))]
+        if let Some(compression) = file.decompress {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "decoding {:?} needs the `decompression` feature",
+                    compression
+                ),
+            ));
+        }
+        Ok(Self {
+            body,
+            url: redact_url(&file.source),
+            check_sum: file.check_sum.clone(),
+            expected_size: file.expected_size,
+            max_file_size,
+            #[cfg(feature = "decompression")]
+            decoder,
+            received_md5,
+            decoded_md5,
+            size: 0,
+            done: false,
+        })
+    }
+
+    fn bad_size(&self, expected_size: u64) -> DlError {
+        check_hash_error(CheckHashError::SizeError(BadSizeErrorDetail {
+            url: self.url.clone(),
+            expected_size,
+            current_size: self.size,
+        }))
+    }
+
+    #[cfg(feature = "decompression")]
+    fn decode_error(&self, err: io::Error) -> DlError {
+        io::Error::new(err.kind(), format!("{}: {}", self.url, err)).into()
+    }
+
+    /// Counts the decoded `chunk` against the limits.
+    fn count(&mut self, chunk: &[u8]) -> Result<(), DlError> {
+        self.size += chunk.len() as u64;
+        if let Some(max_file_size) = self.max_file_size.filter(|max| self.size > *max) {
+            return Err(FileTooLargeError {
+                url: self.url.clone(),
+                max_file_size,
+            }
+            .into());
+        }
+        if let Some(expected_size) = self.expected_size.filter(|size| self.size > *size) {
+            return Err(self.bad_size(expected_size));
+        }
+        if let Some(context) = &mut self.decoded_md5 {
+            context.consume(chunk);
+        }
+        Ok(())
+    }
+
+    /// The decoded bytes of a received `chunk`, possibly none yet.
+    fn decode(&mut self, chunk: Bytes) -> Result<Bytes, DlError> {
+        if let Some(context) = &mut self.received_md5 {
+            context.consume(&chunk);
+        }
+        #[cfg(feature = "decompression")]
+        let chunk = match &mut self.decoder {
+            Some(decoder) => match decoder.write_all(&chunk) {
+                Ok(()) => Bytes::from(std::mem::take(decoder.get_mut())),
+                Err(err) => return Err(self.decode_error(err)),
+            },
+            None => chunk,
+        };
+        self.count(&chunk)?;
+        Ok(chunk)
+    }
+
+    /// The last decoded bytes once the whole body was received, fails when
+    /// its size or checksum does not match.
+    fn end(&mut self) -> Result<Bytes, DlError> {
+        #[cfg(feature = "decompression")]
+        let tail = match self.decoder.take().map(|decoder| decoder.finish()) {
+            Some(Ok(tail)) => Bytes::from(tail),
+            Some(Err(err)) => return Err(self.decode_error(err)),
+            None => Bytes::new(),
+        };
+        #[cfg(not(feature = "decompression"))]
+        let tail = Bytes::new();
+        self.count(&tail)?;
+        if let Some(expected_size) = self.expected_size.filter(|size| *size != self.size) {
+            return Err(self.bad_size(expected_size));
+        }
+        let digest = self.received_md5.take().or(self.decoded_md5.take());
+        if let (CheckSum::Md5(expected_hash), Some(digest)) = (&self.check_sum, digest) {
+            compare_md5(expected_hash, &digest.compute().0, self.url.clone())
+                .map_err(check_hash_error)?;
+        }
+        Ok(tail)
+    }
+}
+
+impl Stream for CheckedBody {
+    type Item = Result<Bytes, DlError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        while !self.done {
+            let chunk = match ready!(self.body.poll_next_unpin(cx)) {
+                Some(Ok(chunk)) => self.decode(chunk),
+                Some(Err(err)) => Err(err),
+                None => {
+                    self.done = true;
+                    self.end()
+                }
+            };
+            match chunk {
+                Ok(chunk) if chunk.is_empty() => continue,
+                Ok(chunk) => return Poll::Ready(Some(Ok(chunk))),
+                Err(err) => {
+                    // stops the transfer
+                    self.body = stream::empty().boxed();
+                    self.done = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// Requests the source of `file` through its [`Hops`] and returns its body
+/// as soon as the headers of a successful response are received.
+///
+/// The body is decoded like `file.decompress` asks and checked while it is
+/// read, see [`CheckedBody`]. Nothing is written to disk: the target,
+/// existing file policy and extraction of the file are ignored.
+pub(crate) async fn stream_file(
+    config: &DownloadConfig,
+    file: &FileToDl,
+    version: HttpVersion,
+) -> Result<BodyStream, DlError> {
+    let mut hops = Hops::new(std::slice::from_ref(file))?;
+    let (response, body) = loop {
+        let request = hops.request(config, file, 0, Method::Get, false).await?;
+        let (response, body) = config.transport.stream_file(&request, version).await?;
+        if !hops.retry(config, 0, &response).await? {
+            break (response, body);
+        }
+    };
+    if !response.is_success() {
+        return Err(HttpStatusError::from(vec![HttpStatusErrorDetail {
+            url: redact_url(&file.source),
+            status: response.status,
+        }])
+        .into());
+    }
+    let max_file_size = file.max_file_size.or(config.max_file_size);
+    // the announced length is not the one of a decoded body
+    if let (Some(max_file_size), None, false) = (
+        max_file_size.filter(|max| {
+            response
+                .header("Content-Length")
+                .and_then(|len| len.parse::<u64>().ok())
+                .is_some_and(|len| len > *max)
+        }),
+        file.decompress,
+        config.accept_encoding,
+    ) {
+        return Err(FileTooLargeError {
+            url: redact_url(&file.source),
+            max_file_size,
+        }
+        .into());
+    }
+    Ok(CheckedBody::new(file, body, max_file_size)?.boxed())
+}
@@ -0,0 +1,176 @@
+//! All-or-nothing download of a folder, see
+//! [`crate::DownloadBuilder::transactional`].
+//!
+//! Every file is staged as a checked `.tmp` sibling of each of its targets
+//! first. The commit then moves each existing target aside to a hidden `.<name>.rollback` file
+//! before renaming the new one in place, and puts everything back if a rename
+//! fails. The handlers of the files with a handler factory are committed
+//! last, once every rename succeeded.
+use super::{
+    backup_path, cache, generate_tmp_files, remove_path, remove_tmp_file, replace_target,
+    spawn_blocking, stage_files, DownloadConfig, DownloadReport, ExistingFilePolicy, FileToDl,
+    HandledFile, Stage, StagedFile,
+};
+use crate::error::DlError;
+use crate::transport::HttpVersion;
+
+#[cfg(feature = "async-std")]
+use async_std::{
+    fs,
+    path::{Path, PathBuf},
+};
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use std::path::{Path, PathBuf};
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use tokio::fs;
+
+pub(super) fn rollback_path(target: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(target.file_name().unwrap_or_default());
+    name.push(".rollback");
+    target.with_file_name(name)
+}
+
+pub(crate) struct Transaction<'a> {
+    config: &'a DownloadConfig,
+    staged: Vec<StagedFile>,
+    handled: Vec<HandledFile>,
+    report: DownloadReport,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(config: &'a DownloadConfig) -> Self {
+        Self {
+            config,
+            staged: Vec::new(),
+            handled: Vec::new(),
+            report: DownloadReport::default(),
+        }
+    }
+
+    /// Downloads and checks `files`, on failure every staged file is
+    /// discarded and the targets are left untouched.
+    pub(crate) async fn stage(
+        &mut self,
+        files: &[FileToDl],
+        version: HttpVersion,
+    ) -> Result<(), DlError> {
+        let error = match stage_files(self.config, files, version).await {
+            Ok(Stage {
+                staged,
+                handled,
+                report,
+                error: None,
+            }) => {
+                self.staged.extend(staged);
+                self.handled.extend(handled);
+                self.report.append(report);
+                return Ok(());
+            }
+            Ok(Stage {
+                error: Some(error), ..
+            }) => error,
+            Err(error) => error,
+        };
+        for tmp_file in generate_tmp_files(files.iter()) {
+            let _ = remove_tmp_file(&tmp_file).await;
+        }
+        self.discard().await;
+        Err(error)
+    }
+
+    async fn discard(&mut self) {
+        for staged in self.staged.drain(..) {
+            let _ = remove_tmp_file(&staged.tmp_file).await;
+        }
+        self.handled.clear();
+    }
+
+    /// Renames every staged file to its target, or restores all of them
+    /// when one rename fails.
+    pub(crate) async fn commit(mut self) -> Result<DownloadReport, DlError> {
+        let mut committed = Vec::with_capacity(self.staged.len());
+        let mut failure = None;
+        'staged: for staged in self.staged.iter() {
+            for (tmp_file, file) in staged.tmp_file.each_target().zip(staged.file.each_target()) {
+                match replace_target(&tmp_file, &file, true, self.config.durable).await {
+                    Ok(aside) => committed.push((staged, file.target, aside)),
+                    Err(err) => {
+                        failure = Some(err);
+                        break 'staged;
+                    }
+                }
+            }
+        }
+        if let Some(err) = failure {
+            for (_, target, aside) in committed.into_iter().rev() {
+                let _ = match aside {
+                    Some(aside) => fs::rename(aside, &target).await,
+                    None => remove_path(&target).await,
+                };
+            }
+            self.discard().await;
+            return Err(err.into());
+        }
+
+        for (staged, target, aside) in committed {
+            if let Some(aside) = aside {
+                if staged.file.existing_file_policy == Some(ExistingFilePolicy::Backup) {
+                    fs::rename(aside, backup_path(&target).await).await?;
+                } else {
+                    remove_path(&aside).await?;
+                }
+            }
+            if self.config.revalidate {
+                staged.store_metadata(&target).await?;
+            }
+            match staged.cached {
+                true => self.report.cached.push(target),
+                false => self.report.downloaded.push(target),
+            }
+        }
+        cache::populate(self.config, self.staged.iter()).await?;
+        for handled in self.handled.drain(..) {
+            spawn_blocking(move || handled.handler.commit()).await?;
+            self.report.downloaded.push(handled.file.target);
+        }
+        Ok(self.report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{config, file};
+    use crate::transport::mock::{MockResponse, MockTransport};
+    use crate::CheckSum;
+
+    #[tokio::test]
+    async fn test_failed_commit_restores_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let transport = MockTransport::default();
+        let config = config(&transport);
+        let mut files = Vec::new();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            let file = file(&dir, name, CheckSum::None);
+            transport.register(file.source.clone(), MockResponse::ok("new"));
+            files.push(file);
+        }
+        std::fs::write(dir.path().join("a.txt"), "old").unwrap();
+        let mut transaction = Transaction::new(&config);
+        transaction
+            .stage(&files, HttpVersion::Http11)
+            .await
+            .unwrap();
+        std::fs::remove_file(dir.path().join("c.txt.tmp")).unwrap();
+
+        assert!(transaction.commit().await.is_err());
+
+        let names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["a.txt"]);
+        assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"old");
+    }
+}
@@ -15,6 +15,8 @@ where
         IterChunk { iter, size }
     }
 
+    // `is_multiple_of` is not stable before Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
     fn chunk_size_bound(&self, size: usize) -> usize {
         if size == 0 || size == usize::MAX {
             size
@@ -64,6 +66,7 @@ pub trait IterChunkExt: Sized + Iterator {
 impl<I: Iterator> IterChunkExt for I {}
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use super::IterChunkExt;
 
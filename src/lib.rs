@@ -1,48 +1,130 @@
 #![forbid(unsafe_code)]
+#[cfg(feature = "curl")]
 pub mod curl_async;
+pub mod auth;
 pub mod error;
+#[cfg(test)]
+mod fixtures;
 pub mod handler;
 pub mod hash;
+pub mod headers;
 pub mod http_client;
 pub mod iter_chunk;
+pub mod metadata;
+pub mod proxy;
+pub mod tls;
+pub mod transport;
 
+use crate::auth::AuthProvider;
 use crate::error::*;
+use crate::handler::HandlerFactory;
 use crate::hash::BinaryReprFormat;
-use http_client::{download_files_http11, download_files_http2, file_exists};
-pub use http_client::{CheckSum, FileToDl};
+use futures::io::{AsyncWrite, AsyncWriteExt};
+use futures::TryStreamExt;
+pub use handler::Compression;
+use headers::HostHeaders;
+use http_client::{
+    dedupe_files, download_files, download_to_bytes, stream_file, DownloadConfig, Transaction,
+};
+pub use http_client::{
+    ArchiveFormat, Cache, CacheLink, CheckSum, CheckSumOf, DownloadReport, ExistingFilePolicy,
+    Extract, FileToDl,
+};
 
 #[cfg(feature = "async-std")]
-use async_std::path::PathBuf;
+use async_std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use iter_chunk::*;
+use proxy::ProxyConfig;
+use std::sync::Arc;
 #[cfg(all(not(feature = "async-std"), feature = "tokio"))]
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::path::Component;
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use tokio::fs;
+use tls::{HostTlsConfig, TlsConfig};
+use transport::{BodyStream, HttpVersion, Transport};
 
 #[derive(Clone)]
 pub struct DownloadFolder {
     path: PathBuf,
     files: Vec<FileToDl>,
-    if_not_exists: bool,
+    existing_file_policy: Option<ExistingFilePolicy>,
 }
 impl DownloadFolder {
+    /// A folder skipping its existing files with `if_not_exists`, else using
+    /// the policy of the builder.
     pub fn new<T: Into<PathBuf>>(path: T, if_not_exists: bool) -> Self {
         let path = path.into();
         DownloadFolder {
             path,
             files: Vec::default(),
-            if_not_exists,
+            existing_file_policy: if_not_exists.then_some(ExistingFilePolicy::Skip),
         }
     }
 
-    pub async fn add_file(&mut self, mut f: FileToDl) {
-        f.target = self.path.join(
-            f.target
-                .strip_prefix(&self.path)
-                .or_else(|_| f.target.strip_prefix("/"))
-                .unwrap_or(&f.target),
-        );
-        if !self.if_not_exists || !file_exists(&f.target).await {
-            self.files.push(f);
+    /// Sets the policy of the files added afterwards without their own.
+    pub fn existing_file_policy(&mut self, policy: ExistingFilePolicy) {
+        self.existing_file_policy = Some(policy);
+    }
+
+    /// Adds `f` with its targets inside the folder, a target starting with the
+    /// folder or with `/` is taken relative to it.
+    ///
+    /// Targets with a `..` component, a drive prefix or a symbolic link among
+    /// their directories inside the folder are rejected, as they could be
+    /// written elsewhere.
+    pub async fn add_file(&mut self, mut f: FileToDl) -> Result<(), UnsafePathError> {
+        f.target = self.inside(&f.target).await?;
+        for target in f.extra_targets.iter_mut() {
+            *target = self.inside(target).await?;
+        }
+        f.existing_file_policy = f.existing_file_policy.or(self.existing_file_policy);
+        self.files.push(f);
+        Ok(())
+    }
+
+    /// `target` inside the folder, see [`DownloadFolder::add_file`].
+    async fn inside(&self, target: &Path) -> Result<PathBuf, UnsafePathError> {
+        let error = |reason| UnsafePathError {
+            folder: std::path::PathBuf::from(self.path.as_os_str()),
+            target: std::path::PathBuf::from(target.as_os_str()),
+            reason,
+        };
+        let relative = target
+            .strip_prefix(&self.path)
+            .or_else(|_| target.strip_prefix("/"))
+            .unwrap_or(target);
+        let mut names = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                Component::CurDir => {}
+                Component::ParentDir => return Err(error(UnsafePathReason::ParentDir)),
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(error(UnsafePathReason::Absolute))
+                }
+            }
+        }
+        let Some((_, dirs)) = names.split_last() else {
+            return Err(error(UnsafePathReason::Empty));
+        };
+        let mut dir = self.path.clone();
+        for name in dirs {
+            dir.push(name);
+            match fs::symlink_metadata(&dir).await {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    return Err(error(UnsafePathReason::SymlinkedParent(
+                        std::path::PathBuf::from(dir.as_os_str()),
+                    )))
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
         }
+        Ok(self.path.join(names.iter().collect::<PathBuf>()))
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &FileToDl> {
@@ -53,7 +135,8 @@ impl DownloadFolder {
 #[derive(Default)]
 pub struct DownloadBuilder {
     folders: Vec<DownloadFolder>,
-    if_not_exists: bool,
+    config: DownloadConfig,
+    transactional: bool,
 }
 
 impl DownloadBuilder {
@@ -61,40 +144,586 @@ impl DownloadBuilder {
         self.folders.push(f);
     }
 
+    /// Replaces the backend used to fetch the files, see [`transport`].
+    pub fn transport<T: Transport + 'static>(&mut self, transport: T) {
+        self.config.transport = Arc::new(transport);
+    }
+
+    /// Writes the body of every file without its own
+    /// [`FileToDl::handler`] to a handler of `factory` instead of its target.
+    ///
+    /// Redirects, authentication, decompression and checks are unchanged,
+    /// the handlers are committed where the files would be renamed and their
+    /// targets are listed in [`DownloadReport::downloaded`]. Existing file
+    /// policies, extraction and revalidation do not apply to them.
+    pub fn handler<F: HandlerFactory + 'static>(&mut self, factory: F) {
+        self.config.handler = Some(Arc::new(factory));
+    }
+
+    pub fn user_agent<T: Into<String>>(&mut self, user_agent: T) {
+        self.config.header_rules.user_agent = Some(user_agent.into());
+    }
+
+    /// Adds a header sent with every request, `Authorization` and `Cookie`
+    /// headers are dropped when a redirect leaves the host of the file.
+    pub fn header<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        self.config
+            .header_rules
+            .headers
+            .push((name.into(), value.into()));
+    }
+
+    /// Adds a header only sent to `host`, even after redirects.
+    pub fn host_header<H: Into<String>, K: Into<String>, V: Into<String>>(
+        &mut self,
+        host: H,
+        name: K,
+        value: V,
+    ) {
+        let host = host.into();
+        let header = (name.into(), value.into());
+        match self
+            .config
+            .header_rules
+            .host_headers
+            .iter_mut()
+            .find(|rule| rule.host.eq_ignore_ascii_case(&host))
+        {
+            Some(rule) => rule.headers.push(header),
+            None => self.config.header_rules.host_headers.push(HostHeaders {
+                host,
+                headers: vec![header],
+            }),
+        }
+    }
+
+    /// Sets the source of the `Authorization` header, see [`auth`].
+    pub fn auth<A: AuthProvider + 'static>(&mut self, provider: A) {
+        self.config.auth = Some(Arc::new(provider));
+    }
+
+    /// Sets the TLS settings of every request, see [`tls`].
+    pub fn tls(&mut self, config: TlsConfig) {
+        self.config.tls.default = config;
+    }
+
+    /// Sets TLS settings only used for `host`, the fields set in `config`
+    /// replace the ones given to [`DownloadBuilder::tls`].
+    pub fn host_tls<H: Into<String>>(&mut self, host: H, config: TlsConfig) {
+        let host = host.into();
+        self.config
+            .tls
+            .hosts
+            .retain(|rule| !rule.host.eq_ignore_ascii_case(&host));
+        self.config.tls.hosts.push(HostTlsConfig { host, config });
+    }
+
+    /// Sets the proxies of both http 1.1 and http 2 downloads, see [`proxy`].
+    pub fn proxy(&mut self, config: ProxyConfig) {
+        self.config.proxy = config;
+    }
+
+    /// Sets what to do with existing targets, folders and files may
+    /// override it.
+    pub fn existing_file_policy(&mut self, policy: ExistingFilePolicy) {
+        self.config.existing_file_policy = policy;
+    }
+
+    /// Sets the unix permissions of the parent directories created for the
+    /// targets, e.g. `0o750`; they are created with the umask otherwise.
+    pub fn directory_mode(&mut self, mode: u32) {
+        self.config.directory_mode = Some(mode);
+    }
+
+    /// Makes every written target survive a crash or a power loss.
+    ///
+    /// The `.tmp` file is synced to disk before it is atomically renamed to
+    /// its target, then the directory is synced so the rename itself is
+    /// persisted. A target is therefore either its previous version or the
+    /// complete, checked new one, never a truncated file. Directory syncing
+    /// only happens on unix. This costs at least two syncs per file.
+    pub fn durable(&mut self) {
+        self.config.durable = true;
+    }
+
+    /// Checks that the files fit on disk before downloading them.
+    ///
+    /// Every chunk of files is first requested with `HEAD`, the announced
+    /// `Content-Length` are summed per file system and compared to its free
+    /// space, an [`InsufficientSpaceError`] is returned before any transfer
+    /// when they do not fit. Files without `Content-Length` are not counted,
+    /// nor the decompressed or extracted ones since the announced size is the
+    /// one of the compressed body. Only checked on unix.
+    pub fn check_free_space(&mut self) {
+        self.config.check_free_space = true;
+    }
+
+    /// Fails the download of files larger than `bytes` with a
+    /// [`FileTooLargeError`], [`FileToDl::max_file_size`] overrides it.
+    ///
+    /// Files announcing a larger `Content-Length` are rejected before their
+    /// body is read, the others are aborted as soon as the limit is crossed.
+    pub fn max_file_size(&mut self, bytes: u64) {
+        self.config.max_file_size = Some(bytes);
+    }
+
+    /// Sends `Accept-Encoding` so servers may compress the responses, they
+    /// are decoded before being written. Only the curl transport negotiates
+    /// an encoding, other transports ignore it.
+    pub fn accept_encoding(&mut self) {
+        self.config.accept_encoding = true;
+    }
+
+    /// Updates either every file of a folder or none of them.
+    ///
+    /// The files of a folder are all downloaded and checked before any target
+    /// is replaced, then renamed one after the other; if a rename fails the
+    /// previous targets are restored. Any failure leaves the folder as it
+    /// was, folders committed before stay updated. A crash during the commit
+    /// may leave the previous version of some targets in hidden
+    /// `.<name>.rollback` files.
+    pub fn transactional(&mut self) {
+        self.transactional = true;
+    }
+
+    /// Same as [`ExistingFilePolicy::Skip`]: existing files with a
+    /// [`CheckSum`] are still downloaded again when they do not match it.
     pub fn if_not_exists(&mut self) {
-        self.if_not_exists = true;
+        self.existing_file_policy(ExistingFilePolicy::Skip);
     }
 
-    /*
+    /// Same as [`ExistingFilePolicy::Overwrite`], the default.
     pub fn if_exists_overwrite(&mut self) {
-        self.if_not_exists = false;
+        self.existing_file_policy(ExistingFilePolicy::Overwrite);
+    }
+
+    /// Only downloads the existing targets again when they changed upstream,
+    /// unless their [`ExistingFilePolicy`] skips them.
+    ///
+    /// The `ETag` and `Last-Modified` of every downloaded file are stored in a
+    /// sidecar, see [`metadata`], and sent back as `If-None-Match` and
+    /// `If-Modified-Since`. Files answered with `304 Not Modified` are listed
+    /// in [`DownloadReport::up_to_date`].
+    pub fn revalidate(&mut self) {
+        self.config.revalidate = true;
+    }
+
+    /// Looks the files up in `cache` before downloading them.
+    ///
+    /// A file whose checksum, or url with [`Cache::by_url`], has an entry is
+    /// materialised from it without request and listed in
+    /// [`DownloadReport::cached`]. The downloaded files are added to the cache
+    /// once checked and renamed, extracted archives are not cached.
+    pub fn cache(&mut self, cache: Cache) {
+        self.config.cache = Some(cache);
     }
-    */
 
     pub fn folder<T: Into<PathBuf>>(&self, p: T) -> DownloadFolder {
-        DownloadFolder::new(p.into(), self.if_not_exists)
+        DownloadFolder::new(p.into(), false)
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &FileToDl> {
         self.folders.iter().flat_map(|f| f.iter())
     }
 
-    pub async fn download_http2(&self) -> Result<(), DlError> {
-        download_files_http2(&self.iter().cloned().collect::<Vec<_>>()).await?;
-        Ok(())
+    /// Downloads the files by chunks of `chunk_size`, or all at once.
+    ///
+    /// A source given several times is fetched once and written to all of
+    /// its targets, within each folder when transactional. A target given
+    /// several times is written once, and fails the download with a
+    /// [`ConflictingTargetsError`] before any transfer when its sources or
+    /// settings differ, or when it has a handler.
+    async fn download(
+        &self,
+        chunk_size: Option<usize>,
+        version: HttpVersion,
+    ) -> Result<DownloadReport, DlError> {
+        let chunks = |files: Vec<FileToDl>| match chunk_size {
+            Some(chunk_size) => files.into_iter().by_chunk(chunk_size).collect(),
+            None => vec![files],
+        };
+        let files = dedupe_files(&self.config, self.iter())?;
+        let mut report = DownloadReport::default();
+        if self.transactional {
+            for folder in self.folders.iter() {
+                let mut transaction = Transaction::new(&self.config);
+                for chunk_files in chunks(dedupe_files(&self.config, folder.iter())?) {
+                    transaction.stage(&chunk_files, version).await?;
+                }
+                report.append(transaction.commit().await?);
+            }
+        } else {
+            for chunk_files in chunks(files) {
+                report.append(download_files(&self.config, &chunk_files, version).await?);
+            }
+        }
+        Ok(report)
+    }
+
+    pub async fn download_http2(&self) -> Result<DownloadReport, DlError> {
+        self.download(None, HttpVersion::Http2).await
+    }
+
+    pub async fn download_http2_by_chunk(
+        &self,
+        chunk_size: usize,
+    ) -> Result<DownloadReport, DlError> {
+        self.download(Some(chunk_size), HttpVersion::Http2).await
+    }
+
+    pub async fn download_http11(&self, chunk_size: usize) -> Result<DownloadReport, DlError> {
+        self.download(Some(chunk_size), HttpVersion::Http11).await
+    }
+
+    /// Downloads the source of `file` into memory, see
+    /// [`DownloadBuilder::download_many_to_bytes`].
+    pub async fn download_to_bytes(
+        &self,
+        file: &FileToDl,
+        version: HttpVersion,
+    ) -> Result<Vec<u8>, DlError> {
+        let mut bodies =
+            download_to_bytes(&self.config, std::slice::from_ref(file), version).await?;
+        Ok(bodies.remove(0))
+    }
+
+    /// Downloads the sources of `files` into memory with the settings of the
+    /// builder, their bodies are returned in the same order.
+    ///
+    /// Statuses, sizes and checksums are checked like for the files written
+    /// to disk and http 2 transfers are multiplexed. Nothing is written: the
+    /// targets, existing file policies and extraction are ignored, as are
+    /// the folders of the builder.
+    pub async fn download_many_to_bytes(
+        &self,
+        files: &[FileToDl],
+        version: HttpVersion,
+    ) -> Result<Vec<Vec<u8>>, DlError> {
+        download_to_bytes(&self.config, files, version).await
     }
 
-    pub async fn download_http2_by_chunk(&self, chunk_size: usize) -> Result<(), DlError> {
-        for chunk_files in self.iter().cloned().by_chunk(chunk_size) {
-            download_files_http2(&chunk_files).await?;
+    /// Streams the source of `file` with the settings of the builder, the
+    /// chunks are yielded as they are received.
+    ///
+    /// The stream is returned once the headers of a successful response are
+    /// received, after the redirects. The transfer waits while the stream is
+    /// not read and stops once it is dropped. The body is decoded and checked
+    /// like in [`DownloadBuilder::download_to_bytes`]: a size or checksum
+    /// mismatch is the last item of the stream, after the whole body.
+    ///
+    /// A custom [`Transport`] without its own
+    /// [`Transport::stream_file`] downloads the whole body in memory first.
+    pub async fn download_stream(
+        &self,
+        file: &FileToDl,
+        version: HttpVersion,
+    ) -> Result<BodyStream, DlError> {
+        stream_file(&self.config, file, version).await
+    }
+
+    /// Writes the source of `file` into `writer` as it is received, see
+    /// [`DownloadBuilder::download_stream`], and returns the number of bytes
+    /// written.
+    ///
+    /// `writer` is flushed but not closed. It may have received the whole
+    /// body when the size or checksum does not match.
+    pub async fn download_into<W: AsyncWrite + Unpin>(
+        &self,
+        file: &FileToDl,
+        version: HttpVersion,
+        writer: &mut W,
+    ) -> Result<u64, DlError> {
+        let mut body = self.download_stream(file, version).await?;
+        let mut written = 0;
+        while let Some(chunk) = body.try_next().await? {
+            writer.write_all(&chunk).await?;
+            written += chunk.len() as u64;
         }
-        Ok(())
+        writer.flush().await?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::file;
+    use crate::transport::mock::{MockResponse, MockTransport};
+
+    #[tokio::test]
+    async fn test_download_to_bytes() {
+        let transport = MockTransport::default();
+        transport.register("https://example.com/index.json", MockResponse::ok("{}"));
+        let mut builder = DownloadBuilder::default();
+        builder.transport(transport.clone());
+        builder.header("X-Client", "test");
+        let file = FileToDl {
+            source: "https://example.com/index.json".to_owned(),
+            ..FileToDl::default()
+        };
+
+        let body = builder
+            .download_to_bytes(&file, HttpVersion::Http2)
+            .await
+            .unwrap();
+
+        assert_eq!(body, b"{}");
+        let requests = transport.requests();
+        assert_eq!(requests[0].version, HttpVersion::Http2);
+        assert!(requests[0]
+            .headers
+            .contains(&("X-Client".to_owned(), "test".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn test_download_into() {
+        let transport = MockTransport::default();
+        transport.register(
+            "https://example.com/a.bin",
+            MockResponse::ok(vec![7; 100_000]),
+        );
+        let mut builder = DownloadBuilder::default();
+        builder.transport(transport.clone());
+        let file = FileToDl {
+            source: "https://example.com/a.bin".to_owned(),
+            expected_size: Some(100_000),
+            ..FileToDl::default()
+        };
+
+        let mut writer = Vec::new();
+        let written = builder
+            .download_into(&file, HttpVersion::Http11, &mut writer)
+            .await
+            .unwrap();
+
+        assert_eq!(written, 100_000);
+        assert_eq!(writer, vec![7; 100_000]);
     }
 
-    pub async fn download_http11(&self, chunk_size: usize) -> Result<(), DlError> {
-        for chunk_files in self.iter().cloned().by_chunk(chunk_size) {
-            download_files_http11(&chunk_files).await?;
+    #[tokio::test]
+    async fn test_builder_uses_transport() {
+        let dir = tempfile::tempdir().unwrap();
+        let transport = MockTransport::default();
+        let mut builder = DownloadBuilder::default();
+        builder.transport(transport.clone());
+        let mut folder = builder.folder(dir.path());
+        for name in ["a", "b", "c"] {
+            let file = file("", name, CheckSum::None);
+            transport.register(file.source.clone(), MockResponse::ok(name));
+            folder.add_file(file).await.unwrap();
         }
-        Ok(())
+        builder.add_folder(folder);
+
+        builder.download_http11(2).await.unwrap();
+
+        assert_eq!(transport.requests().len(), 3);
+        assert_eq!(std::fs::read(dir.path().join("c")).unwrap(), b"c");
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_are_fetched_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let transport = MockTransport::default();
+        transport.register("https://example.com/a", MockResponse::ok("a"));
+        let mut builder = DownloadBuilder::default();
+        builder.transport(transport.clone());
+        for name in ["one", "two"] {
+            let mut folder = builder.folder(dir.path().join(name));
+            for _ in 0..2 {
+                folder.add_file(file("", "a", CheckSum::None)).await.unwrap();
+            }
+            builder.add_folder(folder);
+        }
+
+        let report = builder.download_http2().await.unwrap();
+
+        assert_eq!(transport.requests().len(), 1);
+        assert_eq!(report.downloaded.len(), 2);
+        assert_eq!(std::fs::read(dir.path().join("two/a")).unwrap(), b"a");
+
+        let mut folder = builder.folder(dir.path().join("two"));
+        folder
+            .add_file(FileToDl {
+                source: "https://example.com/b".to_owned(),
+                ..file("", "a", CheckSum::None)
+            })
+            .await
+            .unwrap();
+        builder.add_folder(folder);
+        match builder.download_http2().await {
+            Err(DlError::ConflictingTargetsError(err)) => {
+                assert_eq!(err.targets, [dir.path().join("two/a")])
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    fn target(target: &str) -> FileToDl {
+        file("", target, CheckSum::None)
+    }
+
+    #[tokio::test]
+    async fn test_add_file_keeps_targets_in_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut folder = DownloadFolder::new(dir.path(), false);
+        for name in ["./a//b.txt", "/etc/c.txt"] {
+            folder.add_file(target(name)).await.unwrap();
+        }
+        folder
+            .add_file(target(dir.path().join("d.txt").to_str().unwrap()))
+            .await
+            .unwrap();
+        let targets: Vec<_> = folder.iter().map(|f| f.target.clone()).collect();
+        // the `PathBuf` of the runtime
+        let root = Path::new(dir.path());
+        assert_eq!(
+            targets,
+            vec![
+                root.join("a/b.txt"),
+                root.join("etc/c.txt"),
+                root.join("d.txt")
+            ]
+        );
+
+        for (name, reason) in [
+            ("../../etc/cron.d/x", UnsafePathReason::ParentDir),
+            ("a/../b", UnsafePathReason::ParentDir),
+            (".", UnsafePathReason::Empty),
+        ] {
+            let err = folder.add_file(target(name)).await.unwrap_err();
+            assert_eq!(err.reason, reason);
+        }
+        let escaping = dir.path().join("../x");
+        let err = folder
+            .add_file(target(escaping.to_str().unwrap()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.reason, UnsafePathReason::ParentDir);
+        assert_eq!(folder.iter().count(), 3);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_add_file_rejects_symlinked_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("real/link")).unwrap();
+        let mut folder = DownloadFolder::new(dir.path(), false);
+
+        let err = folder
+            .add_file(target("real/link/x.txt"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.reason,
+            UnsafePathReason::SymlinkedParent(dir.path().join("real/link"))
+        );
+        folder.add_file(target("real/x.txt")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_transactional_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let transport = MockTransport::default();
+        let mut builder = DownloadBuilder::default();
+        builder.transport(transport.clone());
+        builder.transactional();
+        let mut folder = builder.folder(dir.path());
+        for name in ["a", "b", "c"] {
+            std::fs::write(dir.path().join(name), "old").unwrap();
+            let source = format!("https://example.com/{}", name);
+            transport.register(
+                source.clone(),
+                MockResponse {
+                    status: if name == "c" { 404 } else { 200 },
+                    ..MockResponse::ok("new")
+                },
+            );
+            transport.register(source.clone(), MockResponse::ok("new"));
+            folder
+                .add_file(FileToDl {
+                    target: name.into(),
+                    source,
+                    ..FileToDl::default()
+                })
+                .await
+                .unwrap();
+        }
+        builder.add_folder(folder);
+        let contents = |dir: &tempfile::TempDir| {
+            let mut names: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+                .into_iter()
+                .map(|name| {
+                    let content = std::fs::read_to_string(dir.path().join(&name)).unwrap();
+                    (name, content)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let err = builder.download_http11(2).await;
+
+        assert!(matches!(err, Err(DlError::HttpStatusError(_))));
+        let old = ["a", "b", "c"].map(|name| (name.to_owned(), "old".to_owned()));
+        assert_eq!(contents(&dir), old);
+
+        let report = builder.download_http11(2).await.unwrap();
+
+        assert_eq!(report.downloaded.len(), 3);
+        let new = ["a", "b", "c"].map(|name| (name.to_owned(), "new".to_owned()));
+        assert_eq!(contents(&dir), new);
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_policy_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let transport = MockTransport::default();
+        let mut builder = DownloadBuilder::default();
+        builder.transport(transport.clone());
+        builder.if_not_exists();
+        let mut skipping = builder.folder(dir.path().join("skipping"));
+        let mut overwriting = builder.folder(dir.path().join("overwriting"));
+        overwriting.existing_file_policy(ExistingFilePolicy::Overwrite);
+        for folder in [&mut skipping, &mut overwriting] {
+            for name in ["a", "b"] {
+                let source = format!("https://example.com/{}", name);
+                transport.register(source.clone(), MockResponse::ok("new"));
+                folder
+                    .add_file(FileToDl {
+                        target: name.into(),
+                        source,
+                        existing_file_policy: (name == "b").then_some(ExistingFilePolicy::Error),
+                        ..FileToDl::default()
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+        for folder in ["skipping", "overwriting"] {
+            std::fs::create_dir(dir.path().join(folder)).unwrap();
+            std::fs::write(dir.path().join(folder).join("a"), "old").unwrap();
+        }
+        builder.add_folder(skipping);
+        builder.add_folder(overwriting);
+
+        let report = builder.download_http11(4).await.unwrap();
+
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.downloaded.len(), 3);
+        assert_eq!(
+            std::fs::read(dir.path().join("skipping/a")).unwrap(),
+            b"old"
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("overwriting/a")).unwrap(),
+            b"new"
+        );
     }
 }
@@ -0,0 +1,165 @@
+//! Validators of downloaded files, kept in a `.<file name>.meta` sidecar next
+//! to each target so an unchanged file can be revalidated with a conditional
+//! request, see [`crate::DownloadBuilder::revalidate`].
+use crate::headers::Header;
+use crate::transport::Response;
+
+#[cfg(feature = "async-std")]
+use async_std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use std::path::{Path, PathBuf};
+#[cfg(all(not(feature = "async-std"), feature = "tokio"))]
+use tokio::{fs, io};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl FileMetadata {
+    pub fn from_response(response: &Response) -> Self {
+        Self {
+            etag: response.header("ETag").map(str::to_owned),
+            last_modified: response.header("Last-Modified").map(str::to_owned),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// `If-None-Match` and `If-Modified-Since` headers matching the validators.
+    ///
+    /// ```
+    /// use file_download::metadata::FileMetadata;
+    /// let metadata = FileMetadata {
+    ///     etag: Some("\"v1\"".to_owned()),
+    ///     last_modified: None,
+    /// };
+    /// assert_eq!(
+    ///     metadata.conditional_headers(),
+    ///     vec![("If-None-Match".to_owned(), "\"v1\"".to_owned())]
+    /// );
+    /// ```
+    pub fn conditional_headers(&self) -> Vec<Header> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push(("If-None-Match".to_owned(), etag.clone()));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.push(("If-Modified-Since".to_owned(), last_modified.clone()));
+        }
+        headers
+    }
+
+    pub fn sidecar_path(target: &Path) -> PathBuf {
+        let mut name = std::ffi::OsString::from(".");
+        name.push(target.file_name().unwrap_or_default());
+        name.push(".meta");
+        target.with_file_name(name)
+    }
+
+    pub(crate) fn parse(text: &str) -> Self {
+        let mut metadata = Self::default();
+        for line in text.lines() {
+            match line.split_once(": ") {
+                Some(("ETag", value)) => metadata.etag = Some(value.to_owned()),
+                Some(("Last-Modified", value)) => metadata.last_modified = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+        metadata
+    }
+
+    pub(crate) fn serialize(&self) -> String {
+        let mut text = String::new();
+        if let Some(etag) = &self.etag {
+            text.push_str(&format!("ETag: {}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            text.push_str(&format!("Last-Modified: {}\n", last_modified));
+        }
+        text
+    }
+
+    /// Validators stored for `target`, `None` without sidecar.
+    pub async fn load(target: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(Self::sidecar_path(target)).await {
+            Ok(text) => Ok(Some(Self::parse(&text)).filter(|metadata| !metadata.is_empty())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes the sidecar of `target`, or removes it without validators.
+    ///
+    /// The sidecar is written to a `.tmp` file renamed over it, so it is
+    /// never seen half written.
+    pub async fn store(&self, target: &Path) -> io::Result<()> {
+        let sidecar = Self::sidecar_path(target);
+        if self.is_empty() {
+            return match fs::remove_file(&sidecar).await {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+        let mut tmp_name = sidecar.file_name().unwrap_or_default().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_sidecar = sidecar.with_file_name(tmp_name);
+        let result = match fs::write(&tmp_sidecar, self.serialize()).await {
+            Ok(()) => fs::rename(&tmp_sidecar, &sidecar).await,
+            Err(err) => Err(err),
+        };
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_sidecar).await;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let metadata = FileMetadata {
+            etag: Some("W/\"a: b\"".to_owned()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+        };
+        assert_eq!(FileMetadata::parse(&metadata.serialize()), metadata);
+        assert_eq!(
+            FileMetadata::sidecar_path(Path::new("/data/a.txt")),
+            PathBuf::from("/data/.a.txt.meta")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_replaces_the_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = PathBuf::from(dir.path().join("a.txt").into_os_string());
+        let metadata = FileMetadata {
+            etag: Some("\"v2\"".to_owned()),
+            last_modified: None,
+        };
+        fs::write(FileMetadata::sidecar_path(&target), "ETag: \"v1\"\n")
+            .await
+            .unwrap();
+
+        metadata.store(&target).await.unwrap();
+
+        assert_eq!(FileMetadata::load(&target).await.unwrap(), Some(metadata));
+        let names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, [".a.txt.meta"]);
+
+        FileMetadata::default().store(&target).await.unwrap();
+        assert_eq!(FileMetadata::load(&target).await.unwrap(), None);
+    }
+}
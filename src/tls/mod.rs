@@ -0,0 +1,304 @@
+//! TLS settings of the transports: trusted certificates, client identity,
+//! minimum protocol version and public key pinning.
+//!
+//! ```
+//! use file_download::tls::{ClientIdentity, TlsConfig, TlsVersion};
+//! use file_download::DownloadBuilder;
+//!
+//! let mut builder = DownloadBuilder::default();
+//! builder.tls(TlsConfig {
+//!     ca_file: Some("/etc/company/ca.pem".into()),
+//!     min_version: Some(TlsVersion::Tls12),
+//!     ..TlsConfig::default()
+//! });
+//! builder.host_tls(
+//!     "internal.example.com",
+//!     TlsConfig {
+//!         client_identity: Some(ClientIdentity::Pem {
+//!             cert: "client.pem".into(),
+//!             key: "client.key".into(),
+//!             key_password: None,
+//!         }),
+//!         pinned_public_keys: vec!["Ayx8oJ3I9j/lbqOuLq9b5pWfx8kbyCC5D+r4b8M3S2s=".to_owned()],
+//!         ..TlsConfig::default()
+//!     },
+//! );
+//! ```
+use crate::auth::Secret;
+use std::path::PathBuf;
+use url::Url;
+
+#[cfg(test)]
+mod test_server;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls10,
+    Tls11,
+    Tls12,
+    Tls13,
+}
+
+/// Certificate presented to the server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClientIdentity {
+    /// PEM certificate chain and private key.
+    Pem {
+        cert: PathBuf,
+        key: PathBuf,
+        key_password: Option<Secret>,
+    },
+    /// PKCS#12 archive holding both the certificate and the key, only
+    /// supported by the curl transport.
+    Pkcs12 {
+        path: PathBuf,
+        password: Option<Secret>,
+    },
+}
+
+/// TLS settings of a request, unset fields keep the transport defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TlsConfig {
+    /// PEM bundle of trusted CA certificates, replaces the default roots.
+    pub ca_file: Option<PathBuf>,
+    /// Directory of PEM CA certificates, replaces the default roots.
+    pub ca_dir: Option<PathBuf>,
+    pub client_identity: Option<ClientIdentity>,
+    /// The hyper transport only implements TLS 1.2 and 1.3, a lower minimum
+    /// fails with a [`crate::error::TlsError`].
+    pub min_version: Option<TlsVersion>,
+    /// Base64 sha256 digests of the accepted server public keys (DER encoded
+    /// `SubjectPublicKeyInfo`), any key is accepted when empty.
+    pub pinned_public_keys: Vec<String>,
+}
+
+impl TlsConfig {
+    /// `self` with the fields set in `other` replacing its own.
+    fn merged(&self, other: &TlsConfig) -> TlsConfig {
+        TlsConfig {
+            ca_file: other.ca_file.clone().or_else(|| self.ca_file.clone()),
+            ca_dir: other.ca_dir.clone().or_else(|| self.ca_dir.clone()),
+            client_identity: other
+                .client_identity
+                .clone()
+                .or_else(|| self.client_identity.clone()),
+            min_version: other.min_version.or(self.min_version),
+            pinned_public_keys: if other.pinned_public_keys.is_empty() {
+                self.pinned_public_keys.clone()
+            } else {
+                other.pinned_public_keys.clone()
+            },
+        }
+    }
+
+    /// Pins in the libcurl `CURLOPT_PINNEDPUBLICKEY` format.
+    #[cfg(feature = "curl")]
+    pub(crate) fn curl_pinned_public_keys(&self) -> Option<String> {
+        if self.pinned_public_keys.is_empty() {
+            return None;
+        }
+        Some(
+            self.pinned_public_keys
+                .iter()
+                .map(|pin| format!("sha256//{}", pin))
+                .collect::<Vec<_>>()
+                .join(";"),
+        )
+    }
+}
+
+/// TLS settings only used for one host, see [`TlsRules`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostTlsConfig {
+    /// Host name compared case-insensitively, without port.
+    pub host: String,
+    pub config: TlsConfig,
+}
+
+/// TLS settings of a download, the fields set in the matching
+/// [`HostTlsConfig`] replace the ones of `default`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsRules {
+    pub default: TlsConfig,
+    pub hosts: Vec<HostTlsConfig>,
+}
+
+impl TlsRules {
+    /// ```
+    /// use file_download::tls::{HostTlsConfig, TlsConfig, TlsRules, TlsVersion};
+    /// use url::Url;
+    /// let rules = TlsRules {
+    ///     default: TlsConfig {
+    ///         min_version: Some(TlsVersion::Tls12),
+    ///         ..TlsConfig::default()
+    ///     },
+    ///     hosts: vec![HostTlsConfig {
+    ///         host: "a.example.com".to_owned(),
+    ///         config: TlsConfig {
+    ///             ca_file: Some("a.pem".into()),
+    ///             ..TlsConfig::default()
+    ///         },
+    ///     }],
+    /// };
+    /// let a = rules.config_for(&Url::parse("https://A.example.com/f").unwrap());
+    /// assert_eq!(a.min_version, Some(TlsVersion::Tls12));
+    /// assert!(a.ca_file.is_some());
+    /// let b = rules.config_for(&Url::parse("https://b.example.com/f").unwrap());
+    /// assert!(b.ca_file.is_none());
+    /// ```
+    pub fn config_for(&self, url: &Url) -> TlsConfig {
+        let host = url.host_str().unwrap_or_default();
+        self.hosts
+            .iter()
+            .filter(|rule| rule.host.eq_ignore_ascii_case(host))
+            .fold(self.default.clone(), |config, rule| {
+                config.merged(&rule.config)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::test_server::{self, testdata, SERVER_PIN};
+    use super::*;
+    use crate::error::DlError;
+    use crate::transport::{HttpVersion, Method, Request, Response, Transport};
+
+    /// The transports of the build, by name.
+    #[allow(clippy::vec_init_then_push)]
+    fn transports() -> Vec<(&'static str, Box<dyn Transport>)> {
+        let mut transports: Vec<(&'static str, Box<dyn Transport>)> = Vec::new();
+        #[cfg(feature = "curl")]
+        transports.push(("curl", Box::new(crate::transport::CurlTransport)));
+        #[cfg(feature = "hyper")]
+        transports.push(("hyper", Box::<crate::transport::HyperTransport>::default()));
+        transports
+    }
+
+    async fn get(
+        transport: &dyn Transport,
+        port: u16,
+        tls: TlsConfig,
+    ) -> Result<Response, DlError> {
+        let request = Request {
+            method: Method::Get,
+            url: format!("https://localhost:{}/", port),
+            target: PathBuf::new(),
+            extra_targets: Vec::new(),
+            headers: Vec::new(),
+            tls,
+            proxy: None,
+            max_size: None,
+            max_file_size: None,
+            decompress: None,
+            accept_encoding: false,
+            in_memory: true,
+        };
+        let mut responses = transport
+            .download_files(std::slice::from_ref(&request), HttpVersion::Http11)
+            .await?;
+        Ok(responses.remove(0))
+    }
+
+    fn trusting_test_ca() -> TlsConfig {
+        TlsConfig {
+            ca_file: Some(testdata("ca.pem")),
+            ..TlsConfig::default()
+        }
+    }
+
+    fn client_identity(name: &str) -> Option<ClientIdentity> {
+        Some(ClientIdentity::Pem {
+            cert: testdata(&format!("{}.pem", name)),
+            key: testdata(&format!("{}.key", name)),
+            key_password: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_trusted_server() {
+        let port = test_server::start();
+        let tls = TlsConfig {
+            client_identity: client_identity("client"),
+            pinned_public_keys: vec![SERVER_PIN.to_owned()],
+            ..trusting_test_ca()
+        };
+        for (name, transport) in transports() {
+            let response = get(&*transport, port, tls.clone()).await.unwrap();
+            assert_eq!(response.status, 200, "{}", name);
+            assert_eq!(response.body.unwrap().into_inner(), b"hello", "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tls_failures() {
+        let port = test_server::start();
+        let failures = [
+            (
+                "wrong CA",
+                TlsConfig {
+                    ca_file: Some(testdata("other_ca.pem")),
+                    ..TlsConfig::default()
+                },
+            ),
+            (
+                "pin mismatch",
+                TlsConfig {
+                    pinned_public_keys: vec![
+                        "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_owned()
+                    ],
+                    ..trusting_test_ca()
+                },
+            ),
+            (
+                "rejected client certificate",
+                TlsConfig {
+                    client_identity: client_identity("other_client"),
+                    ..trusting_test_ca()
+                },
+            ),
+        ];
+        for (name, transport) in transports() {
+            for (failure, tls) in &failures {
+                match get(&*transport, port, tls.clone()).await {
+                    Err(DlError::TlsError(err)) => {
+                        assert_eq!(err.url, format!("https://localhost:{}/", port))
+                    }
+                    result => panic!("{} with a {}: unexpected {:?}", name, failure, result),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_host_config_replaces_set_fields_only() {
+        let default = TlsConfig {
+            ca_file: Some("default.pem".into()),
+            min_version: Some(TlsVersion::Tls12),
+            pinned_public_keys: vec!["a".to_owned()],
+            ..TlsConfig::default()
+        };
+        let host = TlsConfig {
+            ca_file: Some("host.pem".into()),
+            ..TlsConfig::default()
+        };
+        let merged = default.merged(&host);
+        assert_eq!(merged.ca_file, Some("host.pem".into()));
+        assert_eq!(merged.min_version, Some(TlsVersion::Tls12));
+        assert_eq!(merged.pinned_public_keys, vec!["a".to_owned()]);
+    }
+
+    #[cfg(feature = "curl")]
+    #[test]
+    fn test_curl_pin_format() {
+        let config = TlsConfig {
+            pinned_public_keys: vec!["a".to_owned(), "b".to_owned()],
+            ..TlsConfig::default()
+        };
+        assert_eq!(
+            config.curl_pinned_public_keys().as_deref(),
+            Some("sha256//a;sha256//b")
+        );
+        assert_eq!(TlsConfig::default().curl_pinned_public_keys(), None);
+    }
+}
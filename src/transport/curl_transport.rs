@@ -0,0 +1,349 @@
+use super::{
+    file_collector, finish_response, limited_response, write_dl_error, BodyStream, HttpVersion,
+    Method, Request, Response, Transport,
+};
+use crate::auth::redact_url;
+use crate::curl_async::{DlHttp1Future, DlHttp2Future};
+use crate::error::*;
+use crate::handler::{is_size_limit, FileCollector, StreamCollector};
+use crate::proxy::Proxy;
+use crate::tls::{ClientIdentity, TlsConfig, TlsVersion};
+use curl::easy::{self, Easy2, Handler, List, SslVersion};
+use curl::multi::{Easy2Handle, Multi};
+use futures::channel::{mpsc, oneshot};
+use futures::executor::block_on;
+use futures::future::{try_join_all, BoxFuture, FutureExt};
+use futures::StreamExt;
+use std::thread;
+use std::time::Duration;
+
+/// Chunks buffered by a streamed body before its transfer is paused.
+const STREAM_BUFFER: usize = 8;
+
+/// Transport backed by libcurl, http 1.1 transfers run on one thread per file
+/// and http 2 transfers are multiplexed with a curl `Multi` handle.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CurlTransport;
+
+/// `CURLE_SSL_PINNEDPUBKEYNOTMATCH`, without a dedicated predicate in the curl crate.
+const CURLE_SSL_PINNEDPUBKEYNOTMATCH: u32 = 90;
+
+fn is_tls_error(error: &curl::Error) -> bool {
+    error.is_ssl_connect_error()
+        || error.is_peer_failed_verification()
+        || error.is_ssl_certproblem()
+        || error.is_ssl_cipher()
+        || error.is_ssl_cacert()
+        || error.is_ssl_cacert_badfile()
+        || error.is_ssl_crl_badfile()
+        || error.is_ssl_issuer_error()
+        || error.code() == CURLE_SSL_PINNEDPUBKEYNOTMATCH
+        // with TLS 1.3 a rejected client certificate is an alert received
+        // once the handshake is over
+        || (error.is_recv_error()
+            && error
+                .extra_description()
+                .is_some_and(|extra| extra.contains(" alert ")))
+}
+
+/// Reports certificate and handshake failures as [`DlError::TlsError`].
+fn curl_dl_error(error: CurlError, url: &str) -> DlError {
+    match error {
+        CurlError::CurlError(error) if is_tls_error(&error) => TlsError {
+            url: redact_url(url),
+            message: error.to_string(),
+        }
+        .into(),
+        error => error.into(),
+    }
+}
+
+/// The response of a performed transfer. A failure is the I/O error of its
+/// [`FileCollector`] when writing the file aborted it, a transfer stopped by
+/// the size limit keeps its response.
+fn transfer_response(
+    collector: &mut FileCollector,
+    performed: Result<(), curl::Error>,
+    request: &Request,
+) -> Result<Response, DlError> {
+    match (performed, collector.take_error(), request.max_file_size) {
+        (Ok(()), _, _) => {
+            let response = collector.response().clone();
+            finish_response(collector, &request.url, response)
+        }
+        (Err(_), Some(err), _) if is_size_limit(&err) => {
+            let response = collector.response().clone();
+            Ok(limited_response(collector, response))
+        }
+        (Err(_), Some(err), _) => Err(write_dl_error(err, &request.url)),
+        // rejected by curl from the announced `Content-Length`
+        (Err(error), None, Some(max_file_size)) if error.is_filesize_exceeded() => {
+            Err(FileTooLargeError {
+                url: redact_url(&request.url),
+                max_file_size,
+            }
+            .into())
+        }
+        (Err(error), None, _) => Err(curl_dl_error(error.into(), &request.url)),
+    }
+}
+
+impl From<TlsVersion> for SslVersion {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls10 => SslVersion::Tlsv10,
+            TlsVersion::Tls11 => SslVersion::Tlsv11,
+            TlsVersion::Tls12 => SslVersion::Tlsv12,
+            TlsVersion::Tls13 => SslVersion::Tlsv13,
+        }
+    }
+}
+
+fn set_tls_options<H: Handler>(easy: &mut Easy2<H>, tls: &TlsConfig) -> Result<(), curl::Error> {
+    if let Some(ca_file) = &tls.ca_file {
+        easy.cainfo(ca_file)?;
+    }
+    if let Some(ca_dir) = &tls.ca_dir {
+        easy.capath(ca_dir)?;
+    }
+    match &tls.client_identity {
+        Some(ClientIdentity::Pem {
+            cert,
+            key,
+            key_password,
+        }) => {
+            easy.ssl_cert(cert)?;
+            easy.ssl_cert_type("PEM")?;
+            easy.ssl_key(key)?;
+            easy.ssl_key_type("PEM")?;
+            if let Some(password) = key_password {
+                easy.key_password(password.expose())?;
+            }
+        }
+        Some(ClientIdentity::Pkcs12 { path, password }) => {
+            easy.ssl_cert(path)?;
+            easy.ssl_cert_type("P12")?;
+            if let Some(password) = password {
+                easy.key_password(password.expose())?;
+            }
+        }
+        None => {}
+    }
+    if let Some(min_version) = tls.min_version {
+        easy.ssl_min_max_version(min_version.into(), SslVersion::Default)?;
+    }
+    if let Some(pins) = tls.curl_pinned_public_keys() {
+        easy.pinned_public_key(&pins)?;
+    }
+    Ok(())
+}
+
+fn set_proxy_options<H: Handler>(
+    easy: &mut Easy2<H>,
+    proxy: &Option<Proxy>,
+) -> Result<(), curl::Error> {
+    match proxy {
+        Some(proxy) => {
+            easy.proxy(&proxy.url)?;
+            if let Some(auth) = &proxy.auth {
+                easy.proxy_username(&auth.username)?;
+                easy.proxy_password(auth.password.expose())?;
+            }
+        }
+        // an empty proxy keeps libcurl from reading the environment again
+        None => easy.proxy("")?,
+    }
+    Ok(())
+}
+
+/// Sets the url, method, headers, TLS and proxy options of `request`.
+fn set_request_options<H: Handler>(
+    easy: &mut Easy2<H>,
+    request: &Request,
+    version: HttpVersion,
+) -> Result<(), curl::Error> {
+    easy.url(&request.url)?;
+    // the announced length is not the one of a decoded body
+    if let (Some(max_file_size), None, false) = (
+        request.max_file_size,
+        request.decompress,
+        request.accept_encoding,
+    ) {
+        easy.max_filesize(max_file_size)?;
+    }
+    if request.accept_encoding {
+        easy.accept_encoding("")?;
+    }
+    match request.method {
+        Method::Get => easy.get(true)?,
+        Method::Head => easy.nobody(true)?,
+    }
+    let mut headers = List::new();
+    for (name, value) in request.headers.iter() {
+        headers.append(&format!("{}: {}", name, value))?;
+    }
+    easy.http_headers(headers)?;
+    set_tls_options(easy, &request.tls)?;
+    set_proxy_options(easy, &request.proxy)?;
+    if version == HttpVersion::Http2 {
+        easy.http_version(if request.url.starts_with("https:") {
+            easy::HttpVersion::V2TLS
+        } else {
+            easy::HttpVersion::V2
+        })?;
+    }
+    Ok(())
+}
+
+fn download_file_http_curl(request: &Request) -> Result<Easy2<FileCollector>, curl::Error> {
+    let mut easy: Easy2<_> = file_collector(request).into();
+    set_request_options(&mut easy, request, HttpVersion::Http11)?;
+    Ok(easy)
+}
+
+fn download_file_http2_curl(request: &Request) -> Result<Easy2<FileCollector>, curl::Error> {
+    let mut easy: Easy2<_> = file_collector(request).into();
+    set_request_options(&mut easy, request, HttpVersion::Http2)?;
+    Ok(easy)
+}
+
+async fn download_files_http11_curl(requests: &[Request]) -> Result<Vec<Response>, DlError> {
+    try_join_all(requests.iter().map(|request| async move {
+        let builder_request = request.clone();
+        let (mut easy, performed) = DlHttp1Future::new(move || {
+            download_file_http_curl(&builder_request).map_err(CurlError::from)
+        })
+        .await
+        .map_err(|error| curl_dl_error(error, &request.url))?;
+        transfer_response(easy.get_mut(), performed, request)
+    }))
+    .await
+}
+
+async fn download_files_http2_curl(requests: &[Request]) -> Result<Vec<Response>, DlError> {
+    let mut dl_tokens = Vec::with_capacity(requests.len());
+    let multi = curl::multi::Multi::new();
+    for request in requests.iter() {
+        let easy = download_file_http2_curl(request)
+            .map_err(|error| curl_dl_error(error.into(), &request.url))?;
+        dl_tokens.push(multi.add2(easy)?);
+    }
+    if !dl_tokens.is_empty() {
+        let results = DlHttp2Future::new(dl_tokens.as_slice(), multi)
+            .await
+            .map_err(|_| {
+                CurlError::from(ThreadSafeError {
+                    message: "http2 error".to_owned(),
+                })
+            })?;
+        return results
+            .into_iter()
+            .zip(requests.iter())
+            .zip(dl_tokens.iter_mut())
+            .map(|((result, request), token)| transfer_response(token.get_mut(), result, request))
+            .collect();
+    }
+    Ok(Vec::new())
+}
+
+/// `Multi` handles are not `Send`, the whole multiplexed transfer is driven
+/// from a dedicated thread so the returned future can move between threads.
+async fn download_files_http2_curl_thread(requests: &[Request]) -> Result<Vec<Response>, DlError> {
+    let requests = requests.to_vec();
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let _ = sender.send(block_on(download_files_http2_curl(&requests)));
+    });
+    receiver.await.map_err(|_| {
+        CurlError::from(ThreadSafeError {
+            message: "http2 thread stopped".to_owned(),
+        })
+    })?
+}
+
+/// Performs the transfer of `handle` until it ends or its stream is dropped,
+/// the transfer is resumed as soon as the stream has room for a chunk.
+fn drive_stream(multi: &Multi, handle: &mut Easy2Handle<StreamCollector>) -> Result<(), CurlError> {
+    while multi.perform()? > 0 {
+        if !handle.get_ref().is_paused() {
+            multi.wait(&mut [], Duration::from_secs(1))?;
+        } else if handle.get_mut().wait_ready() {
+            handle.unpause_write()?;
+        } else {
+            // the stream was dropped
+            return Ok(());
+        }
+    }
+    let mut performed = Ok(());
+    multi.messages(|message| {
+        if let Some(result) = message.result_for2(handle) {
+            performed = result;
+        }
+    });
+    Ok(performed?)
+}
+
+fn perform_stream(easy: Easy2<StreamCollector>, request: &Request) {
+    let multi = Multi::new();
+    // a dropped collector fails the response
+    let Ok(mut handle) = multi.add2(easy) else {
+        return;
+    };
+    let result = match (drive_stream(&multi, &mut handle), request.max_file_size) {
+        (Ok(()), _) => Ok(()),
+        // rejected by curl from the announced `Content-Length`
+        (Err(CurlError::CurlError(error)), Some(max_file_size)) if error.is_filesize_exceeded() => {
+            Err(FileTooLargeError {
+                url: redact_url(&request.url),
+                max_file_size,
+            }
+            .into())
+        }
+        (Err(error), _) => Err(curl_dl_error(error, &request.url)),
+    };
+    handle.get_mut().finish(result);
+}
+
+/// Streams the body of `request` from a dedicated thread, `Multi` handles
+/// are not `Send`.
+async fn stream_file_curl(
+    request: &Request,
+    version: HttpVersion,
+) -> Result<(Response, BodyStream), DlError> {
+    let (head_sender, head) = oneshot::channel();
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    let mut easy: Easy2<_> = StreamCollector::new(head_sender, sender).into();
+    set_request_options(&mut easy, request, version)
+        .map_err(|error| curl_dl_error(error.into(), &request.url))?;
+    let request = request.clone();
+    thread::spawn(move || perform_stream(easy, &request));
+    let response = head.await.map_err(|_| {
+        CurlError::from(ThreadSafeError {
+            message: "curl stream thread stopped".to_owned(),
+        })
+    })??;
+    Ok((response, receiver.boxed()))
+}
+
+impl Transport for CurlTransport {
+    fn download_files<'a>(
+        &'a self,
+        requests: &'a [Request],
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<Vec<Response>, DlError>> {
+        match version {
+            HttpVersion::Http11 => download_files_http11_curl(requests).boxed(),
+            HttpVersion::Http2 => download_files_http2_curl_thread(requests).boxed(),
+        }
+    }
+
+    /// Runs one `Multi` transfer per stream, paused while the stream is not
+    /// read.
+    fn stream_file<'a>(
+        &'a self,
+        request: &'a Request,
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<(Response, BodyStream), DlError>> {
+        stream_file_curl(request, version).boxed()
+    }
+}
@@ -0,0 +1,155 @@
+//! Connector of [`super::HyperTransport`] reaching hosts through a [`Proxy`].
+//!
+//! Plain http urls are sent to http proxies in absolute form, https urls go
+//! through a `CONNECT` tunnel and socks proxies are used for both.
+use crate::auth::{redact_url, Secret};
+use crate::proxy::Proxy;
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper::Uri;
+use hyper_util::client::legacy::connect::proxy::{SocksV5, Tunnel};
+use hyper_util::client::legacy::connect::{Connected, Connection, HttpConnector};
+use hyper_util::rt::TokioIo;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+use tower_service::Service;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A connection, to a proxy when `proxied` is true.
+pub(crate) struct ProxyStream {
+    io: TokioIo<TcpStream>,
+    proxied: bool,
+}
+
+impl Connection for ProxyStream {
+    fn connected(&self) -> Connected {
+        self.io.connected().proxy(self.proxied)
+    }
+}
+
+impl Read for ProxyStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl Write for ProxyStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write_vectored(cx, bufs)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ProxyConnector {
+    http: HttpConnector,
+    proxy: Option<Proxy>,
+}
+
+impl ProxyConnector {
+    pub(crate) fn new(proxy: Option<Proxy>) -> Self {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        Self { http, proxy }
+    }
+}
+
+async fn connect(
+    mut http: HttpConnector,
+    proxy: Option<Proxy>,
+    dst: Uri,
+) -> Result<ProxyStream, BoxError> {
+    let Some(proxy) = proxy else {
+        return Ok(ProxyStream {
+            io: http.call(dst).await?,
+            proxied: false,
+        });
+    };
+    let (proxy_url, auth) = proxy.split_credentials()?;
+    let proxy_uri: Uri = proxy_url.as_str().parse()?;
+    let io = match proxy_url.scheme() {
+        "http" if dst.scheme_str() == Some("http") => {
+            return Ok(ProxyStream {
+                io: http.call(proxy_uri).await?,
+                proxied: true,
+            })
+        }
+        "http" => {
+            let mut tunnel = Tunnel::new(proxy_uri, http);
+            if let Some(auth) = auth {
+                tunnel = tunnel.with_auth(auth.header_value().expose().parse()?);
+            }
+            tunnel.call(dst).await?
+        }
+        scheme @ ("socks5" | "socks5h") => {
+            let mut socks = SocksV5::new(proxy_uri, http).local_dns(scheme == "socks5");
+            if let Some(auth) = auth {
+                socks = socks.with_auth(auth.username, auth.password.expose().to_owned());
+            }
+            socks.call(dst).await?
+        }
+        _ => {
+            return Err(format!(
+                "proxy {} is not supported by the hyper transport",
+                redact_url(&proxy.url)
+            )
+            .into())
+        }
+    };
+    Ok(ProxyStream { io, proxied: false })
+}
+
+/// `Proxy-Authorization` of a plain http request sent to an http proxy, other
+/// requests authenticate while the tunnel is opened.
+pub(crate) fn forward_authorization(proxy: &Option<Proxy>, url: &str) -> Option<Secret> {
+    let (proxy_url, auth) = proxy.as_ref()?.split_credentials().ok()?;
+    if proxy_url.scheme() == "http" && url.starts_with("http:") {
+        auth.map(|auth| auth.header_value())
+    } else {
+        None
+    }
+}
+
+impl Service<Uri> for ProxyConnector {
+    type Response = ProxyStream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<ProxyStream, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(BoxError::from)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        Box::pin(connect(self.http.clone(), self.proxy.clone(), dst))
+    }
+}
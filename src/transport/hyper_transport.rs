@@ -0,0 +1,476 @@
+use super::hyper_proxy::{forward_authorization, ProxyConnector};
+use super::{
+    file_collector, finish_response, limited_response, write_dl_error, BodyStream, HttpVersion,
+    Method, Request, Response, Transport,
+};
+use crate::auth::redact_url;
+use crate::error::*;
+use crate::handler::is_size_limit;
+use crate::hash::BASE64_ENGINE;
+use crate::proxy::Proxy;
+use crate::tls::{ClientIdentity, TlsConfig, TlsVersion};
+use base64::Engine as _;
+use futures::future::{try_join_all, BoxFuture, FutureExt};
+use futures::{StreamExt, TryStreamExt};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::{Bytes, Incoming};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, OtherError, RootCertStore,
+    SignatureScheme, SupportedProtocolVersion,
+};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+type HyperClient = Client<HttpsConnector<ProxyConnector>, Empty<Bytes>>;
+type ClientKey = (TlsConfig, Option<Proxy>, HttpVersion);
+
+/// Pure rust transport built on hyper and rustls, it needs to run inside a
+/// tokio runtime.
+///
+/// http 2 is negotiated through ALPN on https urls, plain http urls always
+/// use http 1.1.
+///
+/// Requests with their own [`TlsConfig`] use a rustls configuration built
+/// from it instead of the one given to [`HyperTransport::new`]. PKCS#12
+/// identities, encrypted private keys and https proxies are not supported.
+#[derive(Clone)]
+pub struct HyperTransport {
+    http11: HyperClient,
+    http2: HyperClient,
+    tls_config: ClientConfig,
+    /// Clients of the requests with their own TLS settings or a proxy.
+    clients: Arc<Mutex<HashMap<ClientKey, HyperClient>>>,
+}
+
+impl std::fmt::Debug for HyperTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HyperTransport").finish_non_exhaustive()
+    }
+}
+
+fn webpki_roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    roots
+}
+
+fn webpki_tls_config() -> ClientConfig {
+    ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .expect("ring supports the default protocol versions")
+        .with_root_certificates(webpki_roots())
+        .with_no_client_auth()
+}
+
+/// Accepts a certificate chain only if the public key of the server matches
+/// one of the pins, after the usual webpki checks.
+#[derive(Debug)]
+struct PinnedKeyVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<String>,
+}
+
+impl ServerCertVerifier for PinnedKeyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let cert = webpki::EndEntityCert::try_from(end_entity)
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        let digest = ring::digest::digest(
+            &ring::digest::SHA256,
+            cert.subject_public_key_info().as_ref(),
+        );
+        let pin = BASE64_ENGINE.encode(digest.as_ref());
+        if self.pins.contains(&pin) {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(ThreadSafeError::from(format!(
+                    "public key sha256//{} is not pinned",
+                    pin
+                )))),
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+fn add_pem_certificates(roots: &mut RootCertStore, path: &Path) -> Result<(), String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+    for cert in certs {
+        roots
+            .add(cert)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
+
+/// Builds the rustls configuration of `tls`, errors are meant for [`TlsError`].
+fn tls_client_config(tls: &TlsConfig) -> Result<ClientConfig, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = if tls.ca_file.is_none() && tls.ca_dir.is_none() {
+        webpki_roots()
+    } else {
+        let mut roots = RootCertStore::empty();
+        if let Some(ca_file) = &tls.ca_file {
+            add_pem_certificates(&mut roots, ca_file)?;
+        }
+        if let Some(ca_dir) = &tls.ca_dir {
+            let entries = std::fs::read_dir(ca_dir)
+                .map_err(|err| format!("{}: {}", ca_dir.display(), err))?;
+            for entry in entries {
+                let path = entry
+                    .map_err(|err| format!("{}: {}", ca_dir.display(), err))?
+                    .path();
+                if path.is_file() {
+                    add_pem_certificates(&mut roots, &path)?;
+                }
+            }
+        }
+        roots
+    };
+    // rustls only implements TLS 1.2 and 1.3
+    let versions: &[&SupportedProtocolVersion] = match tls.min_version {
+        Some(version @ (TlsVersion::Tls10 | TlsVersion::Tls11)) => {
+            return Err(format!(
+                "a minimum version of {:?} is not supported by the hyper transport, \
+                 which only implements TLS 1.2 and 1.3",
+                version
+            ))
+        }
+        Some(TlsVersion::Tls13) => &[&rustls::version::TLS13],
+        Some(TlsVersion::Tls12) | None => rustls::DEFAULT_VERSIONS,
+    };
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(versions)
+        .map_err(|err| err.to_string())?;
+    let builder = if tls.pinned_public_keys.is_empty() {
+        builder.with_root_certificates(roots)
+    } else {
+        let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .map_err(|err| err.to_string())?;
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedKeyVerifier {
+                inner,
+                pins: tls.pinned_public_keys.clone(),
+            }))
+    };
+    match &tls.client_identity {
+        None => Ok(builder.with_no_client_auth()),
+        Some(ClientIdentity::Pem {
+            key_password: Some(_),
+            ..
+        }) => Err("encrypted private keys are not supported by the hyper transport".to_owned()),
+        Some(ClientIdentity::Pem { cert, key, .. }) => {
+            let certs = CertificateDer::pem_file_iter(cert)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|err| format!("{}: {}", cert.display(), err))?;
+            let key = PrivateKeyDer::from_pem_file(key)
+                .map_err(|err| format!("{}: {}", key.display(), err))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|err| err.to_string())
+        }
+        Some(ClientIdentity::Pkcs12 { .. }) => {
+            Err("PKCS#12 client identities are only supported by the curl transport".to_owned())
+        }
+    }
+}
+
+fn hyper_client(
+    tls_config: ClientConfig,
+    version: HttpVersion,
+    proxy: Option<Proxy>,
+) -> HyperClient {
+    let connector = HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config)
+        .https_or_http();
+    let proxy = ProxyConnector::new(proxy);
+    let connector = match version {
+        HttpVersion::Http11 => connector.enable_http1().wrap_connector(proxy),
+        HttpVersion::Http2 => connector.enable_all_versions().wrap_connector(proxy),
+    };
+    Client::builder(TokioExecutor::new()).build(connector)
+}
+
+impl HyperTransport {
+    pub fn new(tls_config: ClientConfig) -> Self {
+        Self {
+            http11: hyper_client(tls_config.clone(), HttpVersion::Http11, None),
+            http2: hyper_client(tls_config.clone(), HttpVersion::Http2, None),
+            tls_config,
+            clients: Arc::default(),
+        }
+    }
+
+    fn client(&self, request: &Request, version: HttpVersion) -> Result<HyperClient, TlsError> {
+        let default_tls = request.tls == TlsConfig::default();
+        if default_tls && request.proxy.is_none() {
+            return Ok(match version {
+                HttpVersion::Http11 => self.http11.clone(),
+                HttpVersion::Http2 => self.http2.clone(),
+            });
+        }
+        let mut clients = self.clients.lock().expect("hyper clients");
+        let key = (request.tls.clone(), request.proxy.clone(), version);
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        let tls_config = if default_tls {
+            self.tls_config.clone()
+        } else {
+            tls_client_config(&request.tls).map_err(|message| TlsError {
+                url: redact_url(&request.url),
+                message,
+            })?
+        };
+        let client = hyper_client(tls_config, version, request.proxy.clone());
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+}
+
+/// The rustls error behind a failed request, `io::Error` hides its inner
+/// error from `source` so it is unwrapped explicitly.
+fn tls_failure<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a rustls::Error> {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(tls) = error.downcast_ref::<rustls::Error>() {
+            return Some(tls);
+        }
+        source = match error.downcast_ref::<std::io::Error>() {
+            Some(error) => error
+                .get_ref()
+                .map(|inner| inner as &(dyn std::error::Error + 'static)),
+            None => error.source(),
+        };
+    }
+    None
+}
+
+fn hyper_dl_error(error: hyper_util::client::legacy::Error, url: &str) -> DlError {
+    match tls_failure(&error) {
+        Some(tls) => TlsError {
+            url: redact_url(url),
+            message: tls.to_string(),
+        }
+        .into(),
+        None => HyperError::from(error).into(),
+    }
+}
+
+impl Default for HyperTransport {
+    fn default() -> Self {
+        Self::new(webpki_tls_config())
+    }
+}
+
+/// Sends `request` and returns its response once the headers are received,
+/// with its body still to be read.
+async fn send_request(
+    transport: &HyperTransport,
+    request: &Request,
+    version: HttpVersion,
+) -> Result<(Response, Incoming), DlError> {
+    let client = transport.client(request, version)?;
+    let mut builder = hyper::Request::builder()
+        .method(match request.method {
+            Method::Get => hyper::Method::GET,
+            Method::Head => hyper::Method::HEAD,
+        })
+        .uri(request.url.as_str());
+    for (name, value) in request.headers.iter() {
+        builder = builder.header(name, value);
+    }
+    if let Some(authorization) = forward_authorization(&request.proxy, &request.url) {
+        builder = builder.header("Proxy-Authorization", authorization.expose());
+    }
+    let (parts, body) = client
+        .request(builder.body(Empty::new()).map_err(HyperError::from)?)
+        .await
+        .map_err(|error| hyper_dl_error(error, &request.url))?
+        .into_parts();
+    let mut response = Response::new(parts.status.as_u16().into());
+    response.headers = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+        .collect();
+    Ok((response, body))
+}
+
+async fn download_file_hyper(
+    transport: &HyperTransport,
+    request: &Request,
+    version: HttpVersion,
+) -> Result<Response, DlError> {
+    let (response, mut body) = send_request(transport, request, version).await?;
+    let mut collector = file_collector(request);
+    if let Some(len) = response
+        .header("Content-Length")
+        .and_then(|value| value.parse().ok())
+    {
+        // the announced length is not the one of a decoded body
+        if let (Some(max_file_size), None) = (
+            request.max_file_size.filter(|max| len > *max),
+            request.decompress,
+        ) {
+            return Err(FileTooLargeError {
+                url: redact_url(&request.url),
+                max_file_size,
+            }
+            .into());
+        }
+        collector.set_size_hint(len);
+    }
+    while let Some(frame) = body.frame().await {
+        if let Some(data) = frame.map_err(HyperError::from)?.data_ref() {
+            match collector.write_data(data) {
+                Err(err) if is_size_limit(&err) => {
+                    return Ok(limited_response(&mut collector, response))
+                }
+                Err(err) => return Err(write_dl_error(err, &request.url)),
+                Ok(_) => (),
+            }
+        }
+    }
+    finish_response(&mut collector, &request.url, response)
+}
+
+async fn download_files_hyper(
+    transport: &HyperTransport,
+    requests: &[Request],
+    version: HttpVersion,
+) -> Result<Vec<Response>, DlError> {
+    try_join_all(
+        requests
+            .iter()
+            .map(|request| download_file_hyper(transport, request, version)),
+    )
+    .await
+}
+
+/// The body is read frame by frame as the stream is polled, hyper stops
+/// reading the connection while it is not.
+async fn stream_file_hyper(
+    transport: &HyperTransport,
+    request: &Request,
+    version: HttpVersion,
+) -> Result<(Response, BodyStream), DlError> {
+    let (response, body) = send_request(transport, request, version).await?;
+    let chunks = body
+        .into_data_stream()
+        .map_err(|error| HyperError::from(error).into());
+    Ok((response, chunks.boxed()))
+}
+
+impl Transport for HyperTransport {
+    fn download_files<'a>(
+        &'a self,
+        requests: &'a [Request],
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<Vec<Response>, DlError>> {
+        download_files_hyper(self, requests, version).boxed()
+    }
+
+    fn stream_file<'a>(
+        &'a self,
+        request: &'a Request,
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<(Response, BodyStream), DlError>> {
+        stream_file_hyper(self, request, version).boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_tls_failure_behind_io_errors() {
+        let error = io::Error::other(io::Error::new(
+            io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer),
+        ));
+        assert_eq!(
+            tls_failure(&error),
+            Some(&rustls::Error::InvalidCertificate(
+                CertificateError::UnknownIssuer
+            ))
+        );
+        assert_eq!(tls_failure(&io::Error::other("refused")), None);
+    }
+
+    #[test]
+    fn test_pkcs12_is_rejected() {
+        let tls = TlsConfig {
+            client_identity: Some(ClientIdentity::Pkcs12 {
+                path: "client.p12".into(),
+                password: None,
+            }),
+            ..TlsConfig::default()
+        };
+        assert!(tls_client_config(&tls).is_err());
+    }
+
+    #[test]
+    fn test_old_minimum_versions_are_rejected() {
+        for (version, supported) in [
+            (TlsVersion::Tls10, false),
+            (TlsVersion::Tls11, false),
+            (TlsVersion::Tls12, true),
+            (TlsVersion::Tls13, true),
+        ] {
+            let tls = TlsConfig {
+                min_version: Some(version),
+                ..TlsConfig::default()
+            };
+            assert_eq!(tls_client_config(&tls).is_ok(), supported, "{:?}", version);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! In-process transport for deterministic tests, enabled by the `testing` feature.
+//!
+//! ```
+//! use file_download::transport::mock::{MockResponse, MockTransport};
+//! use file_download::DownloadBuilder;
+//!
+//! let transport = MockTransport::default();
+//! transport.register("https://example.com/a.txt", MockResponse::ok("hello"));
+//! transport.register(
+//!     "https://example.com/missing.txt",
+//!     MockResponse {
+//!         status: 404,
+//!         ..MockResponse::ok("not found")
+//!     },
+//! );
+//! let mut builder = DownloadBuilder::default();
+//! builder.transport(transport.clone());
+//! ```
+use super::{
+    file_collector, finish_response, limited_response, write_dl_error, HttpVersion, Method,
+    Request, Response, Transport,
+};
+use crate::auth::redact_url;
+use crate::error::DlError;
+use crate::handler::is_size_limit;
+use crate::headers::{DebugHeaders, Header};
+use futures::channel::oneshot;
+use futures::future::{try_join_all, BoxFuture, FutureExt};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Size of the writes of a mock body.
+const MOCK_CHUNK_SIZE: usize = 16 * 1024;
+
+/// A canned response, see [`MockTransport::register`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockResponse {
+    pub status: u32,
+    pub headers: Vec<Header>,
+    pub body: Vec<u8>,
+    /// Time waited before the response is sent.
+    pub delay: Duration,
+    /// Interrupts the transfer after this many bytes of `body` were written.
+    pub fail_after: Option<usize>,
+}
+
+impl MockResponse {
+    /// A `200` response with `body`.
+    pub fn ok<T: Into<Vec<u8>>>(body: T) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: Duration::ZERO,
+            fail_after: None,
+        }
+    }
+}
+
+/// A request received by a [`MockTransport`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct MockRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<Header>,
+    pub version: HttpVersion,
+}
+
+impl std::fmt::Debug for MockRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockRequest")
+            .field("method", &self.method)
+            .field("url", &redact_url(&self.url))
+            .field("headers", &DebugHeaders(&self.headers))
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    routes: HashMap<String, VecDeque<MockResponse>>,
+    requests: Vec<MockRequest>,
+}
+
+/// Transport answering from registered responses without any network access.
+///
+/// Clones share their routes and request log, so a clone can be given to a
+/// [`crate::DownloadBuilder`] while the test keeps inspecting the original.
+/// Urls without a registered response get a `404`.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    /// Queues `response` for `url`. Queued responses are served in order and
+    /// the last one is repeated for every later request.
+    pub fn register<T: Into<String>>(&self, url: T, response: MockResponse) {
+        self.state
+            .lock()
+            .expect("mock state")
+            .routes
+            .entry(url.into())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().expect("mock state").requests.clone()
+    }
+
+    fn next_response(&self, request: &Request, version: HttpVersion) -> MockResponse {
+        let mut state = self.state.lock().expect("mock state");
+        state.requests.push(MockRequest {
+            method: request.method,
+            url: request.url.clone(),
+            headers: request.headers.clone(),
+            version,
+        });
+        match state.routes.get_mut(&request.url) {
+            Some(responses) if responses.len() > 1 => responses.pop_front(),
+            Some(responses) => responses.front().cloned(),
+            None => None,
+        }
+        .unwrap_or_else(|| MockResponse {
+            status: 404,
+            ..MockResponse::ok(Vec::new())
+        })
+    }
+}
+
+/// Runtime agnostic sleep, the waker is called from a short lived thread.
+async fn sleep(delay: Duration) {
+    if delay.is_zero() {
+        return;
+    }
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        let _ = sender.send(());
+    });
+    let _ = receiver.await;
+}
+
+/// Answers a `HEAD` request with the headers of `mock`, and its
+/// `Content-Length` unless it is registered.
+fn head_mock(mock: MockResponse) -> Response {
+    let mut response = Response::new(mock.status);
+    response.headers = mock.headers;
+    if response.header("Content-Length").is_none() {
+        response
+            .headers
+            .push(("Content-Length".to_owned(), mock.body.len().to_string()));
+    }
+    response
+}
+
+async fn download_file_mock(mock: MockResponse, request: &Request) -> Result<Response, DlError> {
+    sleep(mock.delay).await;
+    if request.method == Method::Head {
+        return Ok(head_mock(mock));
+    }
+    let mut collector = file_collector(request);
+    collector.set_size_hint(mock.body.len() as u64);
+    let mut response = Response::new(mock.status);
+    response.headers = mock.headers;
+    let body = match mock.fail_after {
+        Some(len) => &mock.body[..len.min(mock.body.len())],
+        None => &mock.body[..],
+    };
+    if !body.is_empty() {
+        // delivered in chunks like a real transfer, so a size limit ends it early
+        for chunk in body.chunks(MOCK_CHUNK_SIZE) {
+            match collector.write_data(chunk) {
+                Err(err) if is_size_limit(&err) => {
+                    return Ok(limited_response(&mut collector, response))
+                }
+                Err(err) => return Err(write_dl_error(err, &request.url)),
+                Ok(_) => (),
+            }
+        }
+    }
+    if mock.fail_after.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            format!("mock transfer of {} interrupted", redact_url(&request.url)),
+        )
+        .into());
+    }
+    finish_response(&mut collector, &request.url, response)
+}
+
+impl Transport for MockTransport {
+    fn download_files<'a>(
+        &'a self,
+        requests: &'a [Request],
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<Vec<Response>, DlError>> {
+        try_join_all(
+            requests
+                .iter()
+                .map(|request| download_file_mock(self.next_response(request, version), request)),
+        )
+        .boxed()
+    }
+}
@@ -0,0 +1,182 @@
+#[cfg(feature = "curl")]
+mod curl_transport;
+#[cfg(feature = "hyper")]
+mod hyper_proxy;
+#[cfg(feature = "hyper")]
+mod hyper_transport;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+mod request;
+mod response;
+
+use crate::auth::redact_url;
+use crate::error::{DlError, FileTooLargeError};
+use crate::handler::{is_size_limit, FileCollector, MaxFileSizeExceeded};
+use bytes::Bytes;
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::io;
+use std::sync::Arc;
+
+#[cfg(feature = "curl")]
+pub use curl_transport::CurlTransport;
+#[cfg(feature = "hyper")]
+pub use hyper_transport::HyperTransport;
+pub use request::{Method, Request};
+pub use response::Response;
+
+#[cfg(not(any(feature = "curl", feature = "hyper")))]
+compile_error!("file_download needs at least one of the `curl` or `hyper` features");
+
+/// Protocol requested by the caller, a transport may fall back to http 1.1
+/// when the server does not negotiate http 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpVersion {
+    Http11,
+    Http2,
+}
+
+/// The chunks of a body read as they arrive, see [`Transport::stream_file`].
+pub type BodyStream = BoxStream<'static, Result<Bytes, DlError>>;
+
+/// A backend able to fetch urls into local files.
+///
+/// Transports only move bytes: requests already point to the `.tmp` targets
+/// and carry their final headers. Redirects must not be followed, status,
+/// redirects, checksum verification and the final rename are done by the
+/// caller so every backend behaves the same.
+///
+/// The returned responses must be in the same order as `requests`.
+pub trait Transport: Send + Sync {
+    fn download_files<'a>(
+        &'a self,
+        requests: &'a [Request],
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<Vec<Response>, DlError>>;
+
+    /// The response of `request` once its headers are received and its body
+    /// as a stream of the received bytes, the target is not written.
+    ///
+    /// The body is neither decoded nor limited, the caller does both. The
+    /// transfer must wait while the stream is not read and stop once it is
+    /// dropped.
+    ///
+    /// The default implementation does not stream: it downloads the whole
+    /// body in memory before returning, then yields it as a single chunk, so
+    /// it has no backpressure and holds the body in memory. Transports able to
+    /// stream should implement it.
+    fn stream_file<'a>(
+        &'a self,
+        request: &'a Request,
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<(Response, BodyStream), DlError>> {
+        // the limits of a decoded body do not apply to the received bytes
+        let (max_size, max_file_size) = match request.decompress {
+            None => (request.max_size, request.max_file_size),
+            Some(_) => (None, None),
+        };
+        let request = Request {
+            max_size,
+            max_file_size,
+            decompress: None,
+            in_memory: true,
+            ..request.clone()
+        };
+        async move {
+            let mut response = self
+                .download_files(std::slice::from_ref(&request), version)
+                .await?
+                .remove(0);
+            let body = Bytes::from(response.body.take().unwrap_or_default().into_inner());
+            let chunks = (!body.is_empty()).then_some(Ok(body));
+            Ok((response, stream::iter(chunks).boxed()))
+        }
+        .boxed()
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn download_files<'a>(
+        &'a self,
+        requests: &'a [Request],
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<Vec<Response>, DlError>> {
+        (**self).download_files(requests, version)
+    }
+
+    fn stream_file<'a>(
+        &'a self,
+        request: &'a Request,
+        version: HttpVersion,
+    ) -> BoxFuture<'a, Result<(Response, BodyStream), DlError>> {
+        (**self).stream_file(request, version)
+    }
+}
+
+/// The collector writing the body of `request` to its targets.
+pub(crate) fn file_collector(request: &Request) -> FileCollector {
+    let mut collector = FileCollector::from(&request.target);
+    for target in request.extra_targets.iter() {
+        collector.add_copy(target);
+    }
+    if let Some(max_size) = request.max_size {
+        collector.set_size_limit(max_size);
+    }
+    if let Some(max_file_size) = request.max_file_size {
+        collector.set_max_file_size(max_file_size);
+    }
+    if let Some(compression) = request.decompress {
+        collector.set_decompression(compression);
+    }
+    if request.in_memory {
+        collector.set_in_memory();
+    }
+    collector
+}
+
+/// `response` of a transfer ended by the size limit, with the body received
+/// so far when it is kept in memory.
+pub(crate) fn limited_response(collector: &mut FileCollector, mut response: Response) -> Response {
+    response.body = collector.take_body();
+    response
+}
+
+/// `response` once the whole body of `url` was received by `collector`, a
+/// truncated compressed body fails.
+pub(crate) fn finish_response(
+    collector: &mut FileCollector,
+    url: &str,
+    mut response: Response,
+) -> Result<Response, DlError> {
+    match collector.finish() {
+        Err(err) if !is_size_limit(&err) => return Err(write_dl_error(err, url)),
+        _ => (),
+    }
+    response.body_md5 = collector.body_md5();
+    response.body = collector.take_body();
+    Ok(response)
+}
+
+/// The error of a transfer to `url` aborted by its file, a
+/// [`FileTooLargeError`] past the max file size.
+pub(crate) fn write_dl_error(err: io::Error, url: &str) -> DlError {
+    match err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<MaxFileSizeExceeded>())
+    {
+        Some(exceeded) => FileTooLargeError {
+            url: redact_url(url),
+            max_file_size: exceeded.max_file_size,
+        }
+        .into(),
+        None => err.into(),
+    }
+}
+
+/// The transport used when none is set: curl when enabled, hyper otherwise.
+pub fn default_transport() -> Arc<dyn Transport> {
+    #[cfg(feature = "curl")]
+    return Arc::new(CurlTransport);
+    #[cfg(all(not(feature = "curl"), feature = "hyper"))]
+    return Arc::new(HyperTransport::default());
+}
@@ -0,0 +1,60 @@
+use crate::auth::redact_url;
+use crate::handler::Compression;
+use crate::headers::{DebugHeaders, Header};
+use crate::proxy::Proxy;
+use crate::tls::TlsConfig;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Method {
+    #[default]
+    Get,
+    /// Only fetches the headers, nothing is written to the target.
+    Head,
+}
+
+/// One request, the body is written to `target` or kept in memory.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub target: PathBuf,
+    /// Other files receiving a copy of the body.
+    pub extra_targets: Vec<PathBuf>,
+    pub headers: Vec<Header>,
+    pub tls: TlsConfig,
+    /// Resolved proxy, `None` to connect directly.
+    pub proxy: Option<Proxy>,
+    /// Largest accepted body, the transfer ends once it is exceeded.
+    pub max_size: Option<u64>,
+    /// Largest accepted file, the transfer fails with
+    /// [`crate::error::FileTooLargeError`] once it is exceeded.
+    pub max_file_size: Option<u64>,
+    /// Decodes the body before writing it to `target`.
+    pub decompress: Option<Compression>,
+    /// Lets the server compress the response with a `Content-Encoding`,
+    /// decoded by the transport.
+    pub accept_encoding: bool,
+    /// Keeps the body in [`super::Response::body`] instead of writing it to
+    /// `target`.
+    pub in_memory: bool,
+}
+
+impl std::fmt::Debug for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Request")
+            .field("method", &self.method)
+            .field("url", &redact_url(&self.url))
+            .field("target", &self.target)
+            .field("extra_targets", &self.extra_targets)
+            .field("headers", &DebugHeaders(&self.headers))
+            .field("tls", &self.tls)
+            .field("proxy", &self.proxy)
+            .field("max_size", &self.max_size)
+            .field("max_file_size", &self.max_file_size)
+            .field("decompress", &self.decompress)
+            .field("accept_encoding", &self.accept_encoding)
+            .field("in_memory", &self.in_memory)
+            .finish()
+    }
+}
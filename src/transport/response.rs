@@ -0,0 +1,92 @@
+use crate::handler::BinaryCollector;
+
+/// Status line and headers of the last response received for a file,
+/// intermediate redirect responses are discarded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Response {
+    pub status: u32,
+    pub headers: Vec<(String, String)>,
+    /// MD5 of the received body before it is decoded, only computed for
+    /// files decompressed while they are downloaded.
+    pub body_md5: Option<[u8; 16]>,
+    /// The body of a request kept in memory, see [`super::Request::in_memory`].
+    pub body: Option<BinaryCollector>,
+}
+
+impl Response {
+    pub fn new(status: u32) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body_md5: None,
+            body: None,
+        }
+    }
+
+    /// First value of the header `name`, names are compared case-insensitively.
+    ///
+    /// ```
+    /// use file_download::transport::Response;
+    /// let mut r = Response::new(200);
+    /// r.headers.push(("ETag".to_owned(), "\"abc\"".to_owned()));
+    /// assert_eq!(r.header("etag"), Some("\"abc\""));
+    /// assert_eq!(r.header("last-modified"), None);
+    /// ```
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// `true` for 2xx responses, a status of 0 means the protocol has no
+    /// status (`file://` for example) and is considered a success.
+    pub fn is_success(&self) -> bool {
+        self.status == 0 || (200..300).contains(&self.status)
+    }
+
+    /// Target of a redirect response, `None` for any other response.
+    pub fn redirect_location(&self) -> Option<&str> {
+        match self.status {
+            301 | 302 | 303 | 307 | 308 => self.header("Location"),
+            _ => None,
+        }
+    }
+
+    /// Feeds one raw header line, as received by curl, a status line starts a
+    /// new response.
+    #[cfg(feature = "curl")]
+    pub(crate) fn push_header_line(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end();
+        if line.starts_with("HTTP/") {
+            *self = Self::new(
+                line.split_whitespace()
+                    .nth(1)
+                    .and_then(|status| status.parse().ok())
+                    .unwrap_or_default(),
+            );
+        } else if let Some((name, value)) = line.split_once(':') {
+            self.headers
+                .push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "curl"))]
+mod test {
+    use super::Response;
+
+    #[test]
+    fn test_push_header_line() {
+        let mut r = Response::default();
+        r.push_header_line(b"HTTP/1.1 302 Found\r\n");
+        r.push_header_line(b"Location: /b\r\n");
+        r.push_header_line(b"\r\n");
+        r.push_header_line(b"HTTP/2 200\r\n");
+        r.push_header_line(b"content-length: 12\r\n");
+        assert_eq!(r.status, 200);
+        assert_eq!(r.header("Location"), None);
+        assert_eq!(r.header("Content-Length"), Some("12"));
+    }
+}